@@ -1,22 +1,31 @@
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
-use dialoguer::{Confirm, theme::ColorfulTheme};
-use reqwest::blocking::{Client, multipart};
+use clap::{error::ErrorKind, ArgAction, Args, Parser, Subcommand, ValueEnum};
+use dialoguer::{Confirm, Input, Select, theme::ColorfulTheme};
+use flate2::write::GzEncoder;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use indicatif::{ProgressBar, ProgressStyle};
+use reqwest::blocking::{Client, ClientBuilder, RequestBuilder, Response, multipart};
 use serde::{Deserialize, Serialize};
 use semver::Version;
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
 use std::fs::File;
-use std::io::{Read, Write, copy};
-use std::path::{Path, PathBuf};
+use std::io::{BufRead, BufReader, IsTerminal, Read, Seek, Write, copy};
+use std::path::{Component, Path, PathBuf};
 use std::process::Command;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Condvar, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tempfile::tempdir;
 use walkdir::WalkDir;
 use zip::{write::FileOptions, ZipWriter};
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 struct SubmissionConfig {
-    // Required fields
+    // Required, but may instead come from --api-key or OPTIMUS_API_KEY
+    #[serde(default)]
     api_key: String,
     
     // Optional fields
@@ -26,26 +35,226 @@ struct SubmissionConfig {
     #[serde(default)]
     format: Option<String>,
     
-    #[serde(default = "default_server_url")]
+    #[serde(default = "default_server_url", skip_serializing_if = "is_default_server_url")]
     server_url: String,
     
+    /// Whether `server_url` was set explicitly rather than falling back to the default
+    #[serde(skip)]
+    server_url_set: bool,
+    
     #[serde(default = "default_compression_level")]
     compression_level: u8,
     
+    /// Zip compression method: stored, deflate, bzip2 or zstd (default deflate)
+    #[serde(default)]
+    compression_method: Option<String>,
+    
+    /// Archive container to upload: zip (default) or targz
+    #[serde(default)]
+    archive_format: ArchiveFormat,
+    
     #[serde(default)]
     exclude: Vec<String>,
     
+    /// Largest archive the competition accepts, checked before uploading
+    #[serde(default)]
+    max_archive_bytes: Option<u64>,
+    
+    /// Globs of the only files to archive, replacing the format's file filter (empty = everything)
+    #[serde(default)]
+    include: Vec<String>,
+    
+    /// Extra globs for files that look like secrets, added to the built-in list
+    #[serde(default)]
+    secret_patterns: Vec<String>,
+    
+    #[serde(default = "default_pool_idle_timeout_secs")]
+    pool_idle_timeout_secs: u64,
+    
+    /// Seconds to wait for the server's check (and competition list) response
+    #[serde(default = "default_check_timeout_secs")]
+    check_timeout_secs: u64,
+    
+    /// Seconds the whole upload may take before it is abandoned
+    #[serde(default = "default_upload_timeout_secs")]
+    upload_timeout_secs: u64,
+    
+    /// Proxy for server requests: http://, https://, socks5:// or socks5h:// URL
+    #[serde(default)]
+    proxy: Option<String>,
+    
+    /// Accept invalid or self-signed TLS certificates from the server (never for updates)
+    #[serde(default)]
+    allow_insecure_tls: bool,
+    
+    /// Releases `optimus update` installs: stable (default) or prerelease
+    #[serde(default)]
+    update_channel: UpdateChannel,
+    
+    /// Path every endpoint is mounted under, between `server_url` and the endpoint paths (e.g. `/api/v2`)
+    #[serde(default)]
+    api_prefix: Option<String>,
+    
+    /// Path of the health endpoint checked before building the archive
+    #[serde(default = "default_health_path")]
+    health_path: String,
+    
+    /// Path of the check endpoint, relative to `server_url` and `api_prefix` (e.g. `/v2/check`)
+    #[serde(default = "default_check_path")]
+    check_path: String,
+    
+    /// Path of the submit endpoint, relative to `server_url` and `api_prefix` (e.g. `/v2/submit`)
+    #[serde(default = "default_submit_path")]
+    submit_path: String,
+    
+    /// Extra HTTP status codes to retry, on top of 502/503/504 and connection errors
+    #[serde(default)]
+    retry_on: Vec<u16>,
+    
+    #[serde(default)]
+    archive_name: Option<String>,
+    
+    #[serde(default)]
+    min_interval: Option<String>,
+    
+    #[serde(default)]
+    cancel_on_failure: bool,
+    
+    /// Refuse to submit from a git work tree with uncommitted or untracked changes
+    #[serde(default)]
+    require_clean_git: bool,
+    
+    /// With `require_clean_git`, fail instead of skipping the check outside a git repository
+    #[serde(default)]
+    require_git_repo: bool,
+    
+    /// Whether directories left with no files after filtering are still archived
+    #[serde(default = "default_true")]
+    include_empty_dirs: bool,
+    
+    /// Archive what symlinks point to instead of skipping them; targets must stay inside the root
+    #[serde(default)]
+    follow_symlinks: bool,
+    
+    #[serde(default, alias = "profiles")]
+    environments: HashMap<String, EnvironmentOverlay>,
+    
+    /// Friendly names for competition IDs, e.g. `spring-contest: 8f3a...`
+    #[serde(default)]
+    aliases: BTreeMap<String, String>,
+    
+    #[serde(default)]
+    auth_scheme: AuthScheme,
+    
+    /// Algorithm for the archive checksum sent with the upload
+    #[serde(default)]
+    hash_algo: HashAlgo,
+    
+    #[serde(default)]
+    username: Option<String>,
+    
+    #[serde(default)]
+    auth_header: Option<String>,
+    
     #[serde(default)]
     preferences: Preferences,
 }
 
+/// How the API key is presented to the server
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+enum AuthScheme {
+    /// `Authorization: Bearer <api_key>`
+    #[default]
+    Bearer,
+    /// `Authorization: Basic base64(<username>:<api_key>)`
+    Basic,
+    /// `<auth_header>: <api_key>`
+    Header,
+    /// No credentials are sent
+    None,
+}
+
+/// Digest used for the archive checksum sent to the server
+#[derive(ValueEnum, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+enum HashAlgo {
+    #[default]
+    Sha256,
+    Sha512,
+    Blake3,
+}
+
+impl HashAlgo {
+    /// Name sent to the server in the `checksum_algo` field
+    fn as_str(self) -> &'static str {
+        match self {
+            HashAlgo::Sha256 => "sha256",
+            HashAlgo::Sha512 => "sha512",
+            HashAlgo::Blake3 => "blake3",
+        }
+    }
+}
+
+/// Per-environment values (e.g. staging, prod) overlaid on the base config with `--env`.
+///
+/// Also written as `profiles` and selected with `--profile`, e.g. one per competition.
 #[derive(Deserialize, Serialize, Debug, Clone, Default)]
+struct EnvironmentOverlay {
+    #[serde(default)]
+    server_url: Option<String>,
+    
+    #[serde(default)]
+    api_key: Option<String>,
+    
+    /// Name of an environment variable holding the API key for this environment
+    #[serde(default)]
+    api_key_env: Option<String>,
+    
+    #[serde(default)]
+    competition_id: Option<String>,
+    
+    #[serde(default)]
+    format: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 struct Preferences {
     #[serde(default)]
     auto_confirm: bool,
     
     #[serde(default = "default_true")]
     save_history: bool,
+    
+    /// Show an approximate upload time before sending (may probe the connection first)
+    #[serde(default)]
+    estimate_upload_time: bool,
+    
+    /// Most history records to keep; older ones are pruned first
+    #[serde(default = "default_history_limit")]
+    history_limit: usize,
+    
+    /// Leave out files ignored by `.gitignore` (explicit excludes still apply on top)
+    #[serde(default)]
+    respect_gitignore: bool,
+    
+    /// Keep the temporary archive after a successful upload instead of deleting it
+    #[serde(default)]
+    keep_archive: bool,
+}
+
+// Matches the serde defaults, for configs without a `preferences` section
+impl Default for Preferences {
+    fn default() -> Self {
+        Preferences {
+            auto_confirm: false,
+            save_history: true,
+            estimate_upload_time: false,
+            history_limit: default_history_limit(),
+            respect_gitignore: false,
+            keep_archive: false,
+        }
+    }
 }
 
 fn default_true() -> bool {
@@ -56,23 +265,293 @@ fn default_server_url() -> String {
     "http://localhost:3000".to_string()
 }
 
+fn is_default_server_url(url: &String) -> bool {
+    *url == default_server_url()
+}
+
 fn default_compression_level() -> u8 {
     6
 }
 
-#[derive(Deserialize, Debug)]
+fn default_pool_idle_timeout_secs() -> u64 {
+    90
+}
+
+fn default_check_timeout_secs() -> u64 {
+    10
+}
+
+fn default_upload_timeout_secs() -> u64 {
+    600
+}
+
+fn default_health_path() -> String {
+    "/health".to_string()
+}
+
+fn default_check_path() -> String {
+    "/check".to_string()
+}
+
+fn default_submit_path() -> String {
+    "/submit".to_string()
+}
+
+fn default_history_limit() -> usize {
+    500
+}
+
+/// Warnings raised during the run, checked by `--fail-on-warnings`
+static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// When `--fail-on-warnings` turns recorded warnings into a failure
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum WarningPolicy {
+    /// Finish the run (including the upload), then exit non-zero
+    After,
+    /// Abort before anything is uploaded
+    BeforeUpload,
+}
+
+/// Whether progress chatter is suppressed (`-q`, `--quiet-success` / `--silent`)
+static QUIET_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Whether `-v` asked for request timings, file counts and resolved settings
+static VERBOSE_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Whether `--json` is active, which moves human-readable messages to stderr
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// `--config-format`, overriding the format detected from a config file's extension
+static CONFIG_FORMAT: Mutex<Option<ConfigFormat>> = Mutex::new(None);
+
+/// `println!` for human-readable messages, sent to stderr under `--json` so stdout stays parseable
+macro_rules! message {
+    ($($arg:tt)*) => {
+        if JSON_OUTPUT.load(Ordering::Relaxed) {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// `message!` for progress messages that `-q`, `--quiet-success` and `--silent` hide
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if !QUIET_OUTPUT.load(Ordering::Relaxed) {
+            message!($($arg)*);
+        }
+    };
+}
+
+/// `message!` for diagnostic detail that only `-v` shows
+macro_rules! detail {
+    ($($arg:tt)*) => {
+        if VERBOSE_OUTPUT.load(Ordering::Relaxed) && !QUIET_OUTPUT.load(Ordering::Relaxed) {
+            message!("   {}", format!($($arg)*));
+        }
+    };
+}
+
+/// Destination of `--log-file` records
+enum LogSink {
+    Plain(std::io::BufWriter<File>),
+    Gzip(GzEncoder<std::io::BufWriter<File>>),
+}
+
+impl LogSink {
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            LogSink::Plain(writer) => writer,
+            LogSink::Gzip(encoder) => encoder,
+        }
+    }
+}
+
+/// The open `--log-file`, if any
+static LOG_SINK: Mutex<Option<LogSink>> = Mutex::new(None);
+
+/// A server exchange recorded in the `--log-file`
+#[derive(Serialize, Debug)]
+struct LogRecord<'a> {
+    timestamp: u64,
+    kind: &'a str,
+    url: &'a str,
+    status: u16,
+    body: &'a str,
+}
+
+/// Open the `--log-file` for appending, compressing with gzip when the path ends in `.gz`
+fn open_log(path: &str) -> Result<()> {
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open log file {}", path))?;
+    let writer = std::io::BufWriter::new(file);
+    
+    // Appending to a .gz starts a new gzip member, which decompressors read as one stream
+    let sink = if path.ends_with(".gz") {
+        LogSink::Gzip(GzEncoder::new(writer, flate2::Compression::default()))
+    } else {
+        LogSink::Plain(writer)
+    };
+    
+    *LOG_SINK.lock().unwrap_or_else(|e| e.into_inner()) = Some(sink);
+    Ok(())
+}
+
+/// Append a record to the `--log-file`, if one is open.
+///
+/// Each record is flushed (a sync flush for gzip) so what was logged survives a crash.
+fn log_record(kind: &str, url: &str, status: u16, body: &str) {
+    let mut sink = LOG_SINK.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(sink) = sink.as_mut() else {
+        return;
+    };
+    
+    let record = LogRecord { timestamp: unix_now(), kind, url, status, body };
+    let written = serde_json::to_string(&record)
+        .map_err(anyhow::Error::from)
+        .and_then(|line| {
+            let writer = sink.writer();
+            writeln!(writer, "{}", line)?;
+            writer.flush()?;
+            Ok(())
+        });
+    if let Err(err) = written {
+        eprintln!("⚠️ Failed to write to log file: {}", err);
+    }
+}
+
+/// Flush and finalize the `--log-file`, writing the gzip trailer if compressed
+fn close_log() {
+    let sink = LOG_SINK.lock().unwrap_or_else(|e| e.into_inner()).take();
+    let closed = match sink {
+        Some(LogSink::Plain(mut writer)) => writer.flush(),
+        Some(LogSink::Gzip(encoder)) => encoder.finish().and_then(|mut writer| writer.flush()),
+        None => Ok(()),
+    };
+    if let Err(err) = closed {
+        eprintln!("⚠️ Failed to finalize log file: {}", err);
+    }
+}
+
+/// Print a warning and record it for `--fail-on-warnings`
+fn warn(message: impl Into<String>) {
+    let message = message.into();
+    message!("⚠️ {}", message);
+    
+    if let Ok(mut warnings) = WARNINGS.lock() {
+        warnings.push(message);
+    }
+}
+
+/// Fail if any warnings have been recorded so far
+fn check_warnings() -> Result<()> {
+    let warnings = WARNINGS.lock().map(|w| w.clone()).unwrap_or_default();
+    
+    if warnings.is_empty() {
+        return Ok(());
+    }
+    
+    Err(anyhow::anyhow!(
+        "{} warning(s) emitted with --fail-on-warnings set:\n  - {}",
+        warnings.len(),
+        warnings.join("\n  - ")
+    ))
+}
+
+#[derive(Deserialize, Serialize, Debug)]
 struct CheckResponse {
     submission_approved: bool,
     required_format: String,
     remaining_attempts: i32,
     last_submission_by_user: Option<u64>,
     competition_name: Option<String>,
+    base_manifest_url: Option<String>,
+    /// Paths the organizers forbid, applied on top of the config's excludes
+    server_excludes: Option<Vec<String>>,
+    /// Whether the server accepts chunked uploads via `/submit/chunk` and `/submit/complete`
+    supports_chunked: Option<bool>,
+    /// Seconds the server requires between a user's submissions
+    cooldown_secs: Option<u64>,
+}
+
+impl CheckResponse {
+    /// Time left before the server allows another submission, if it is still cooling down
+    fn cooldown_remaining(&self, now: u64) -> Option<Duration> {
+        let remaining = cooldown_remaining(self.last_submission_by_user?, self.cooldown_secs?, now);
+        (remaining > 0).then_some(Duration::from_secs(remaining))
+    }
+}
+
+/// Seconds left of a cooldown that started at `last_submission`, or 0 once it has passed.
+///
+/// A last submission stamped in the future (clock skew) counts as just now rather than
+/// extending the wait.
+fn cooldown_remaining(last_submission: u64, cooldown_secs: u64, now: u64) -> u64 {
+    cooldown_secs.saturating_sub(now.saturating_sub(last_submission))
+}
+
+/// Server-enforced excludes remembered from the last check
+#[derive(Deserialize, Serialize, Debug)]
+struct CachedServerExcludes {
+    excludes: Vec<String>,
+    fetched_at: u64,
+}
+
+/// A single record in the local submission history (`~/.optimus/history.jsonl`)
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+struct HistoryEntry {
+    #[serde(default)]
+    timestamp: u64,
+
+    #[serde(default)]
+    competition_id: Option<String>,
+
+    #[serde(default)]
+    format: Option<String>,
+
+    #[serde(default)]
+    archive_size: Option<u64>,
+
+    #[serde(default)]
+    submission_id: Option<String>,
+
+    /// HTTP status the server answered the upload with
+    #[serde(default)]
+    server_status: Option<u16>,
+
+    #[serde(default)]
+    sha256: Option<String>,
+
+    #[serde(default)]
+    note: Option<String>,
+
+    #[serde(default)]
+    label: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
 struct GithubRelease {
     tag_name: String,
     assets: Vec<GithubAsset>,
+    
+    #[serde(default)]
+    draft: bool,
+}
+
+/// Which releases `optimus update` considers
+#[derive(ValueEnum, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+enum UpdateChannel {
+    /// Only the latest full release
+    #[default]
+    Stable,
+    /// The highest version, release candidates included
+    Prerelease,
 }
 
 #[derive(Deserialize, Debug)]
@@ -81,9 +560,81 @@ struct GithubAsset {
     browser_download_url: String,
 }
 
+/// Installer chosen from a release, with its published checksum if there is one
+#[derive(Debug, Clone)]
+struct UpdateAsset {
+    download_url: String,
+    /// URL of the `<installer>.sha256` asset
+    checksum_url: Option<String>,
+}
+
+/// Version and build metadata captured by `build.rs`
+#[derive(Serialize, Debug)]
+struct VersionInfo {
+    version: &'static str,
+    commit: &'static str,
+    build_date: String,
+    target: &'static str,
+    features: Vec<&'static str>,
+}
+
+/// Optional capabilities compiled into this build, from the crate's Cargo features
+fn enabled_features() -> Vec<&'static str> {
+    // Deflate is always built in; the rest can be turned off with --no-default-features
+    [
+        ("deflate", true),
+        ("bzip2", cfg!(feature = "bzip2")),
+        ("zstd", cfg!(feature = "zstd")),
+        ("socks", cfg!(feature = "socks")),
+    ]
+    .into_iter()
+    .filter_map(|(name, enabled)| enabled.then_some(name))
+    .collect()
+}
+
+fn version_info() -> VersionInfo {
+    let build_timestamp = env!("OPTIMUS_BUILD_TIMESTAMP").parse().unwrap_or(0);
+    
+    VersionInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        commit: env!("OPTIMUS_GIT_COMMIT"),
+        build_date: format_date(build_timestamp),
+        target: env!("OPTIMUS_TARGET"),
+        features: enabled_features(),
+    }
+}
+
+fn print_version_info(info: &VersionInfo) {
+    println!("optimus {}", info.version);
+    println!("   Commit:   {}", info.commit);
+    println!("   Built:    {}", info.build_date);
+    println!("   Target:   {}", info.target);
+    println!("   Features: {}", info.features.join(", "));
+}
+
 #[derive(Parser, Debug)]
 #[clap(name = "optimus", about = "CLI tool to zip directories and submit them", author, version)]
 struct Cli {
+    /// Append server request/response records to this file as JSON lines (gzip-compressed if it ends in .gz)
+    #[arg(long, global = true)]
+    log_file: Option<String>,
+
+    /// Show more detail, such as request timings, file counts and resolved settings
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Show less: -q prints only errors and the final result, -qq only errors
+    #[arg(short, long, global = true, action = ArgAction::Count, conflicts_with = "verbose")]
+    quiet: u8,
+
+    /// Print the result as a single JSON object on stdout, with messages on stderr
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Parse config files as yaml, toml or json instead of detecting it from the extension
+    #[arg(long, global = true, value_enum)]
+    config_format: Option<ConfigFormat>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -91,652 +642,7882 @@ struct Cli {
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Zip the current directory and send it to the server
-    Send {
-        /// Path to the submission.yml config file
+    Send(Box<SendArgs>),
+
+    /// Build and checksum a submission into a directory without uploading it
+    Prepare(PrepareArgs),
+
+    /// Initialize a new submission.yml configuration file
+    Init {
+        /// Path to create the submission.yml config file
         #[arg(long, default_value = "submission.yml")]
         config: String,
 
-        /// Competition ID (overrides config file)
+        /// API key for authentication
         #[arg(long)]
+        api_key: Option<String>,
+
+        /// Competition ID (also accepted as --competition)
+        #[arg(long, visible_alias = "competition")]
         competition_id: Option<String>,
+    },
 
-        /// API key for authentication (overrides config file)
-        #[arg(long)]
-        api_key: Option<String>,
+    /// Scaffold the files a submission format expects (e.g. main.py, requirements.txt)
+    Template {
+        /// Submission format to scaffold (repo or py)
+        format: String,
 
-        /// Base URL for the server (overrides config file)
-        #[arg(long)]
-        server: Option<String>,
+        /// Path to create the submission.yml config file
+        #[arg(long, default_value = "submission.yml")]
+        config: String,
+    },
+
+    /// Print the files that would be submitted as a directory tree
+    Tree {
+        /// Path to the submission.yml config file (used for exclusions and format if present)
+        #[arg(long, default_value = "submission.yml")]
+        config: String,
 
-        /// Compression level (0-9, overrides config file)
+        /// Submission format to list (repo or py, defaults to the config's format or repo)
         #[arg(long)]
-        compression: Option<u8>,
+        format: Option<String>,
+    },
+
+    /// Check an existing zip archive against a format's rules
+    Verify {
+        /// Path to the zip archive to check
+        archive: String,
 
-        /// Skip server check and force a specific format (repo or py) (overrides config file)
+        /// Submission format to check against (repo or py)
         #[arg(long)]
-        force_format: Option<String>,
+        format: String,
 
-        /// Auto-confirm submission without prompting (overrides config file)
+        /// Also reject files the format wouldn't include (e.g. non-Python files for py)
         #[arg(long)]
-        auto_confirm: bool,
+        strict: bool,
     },
 
-    /// Initialize a new submission.yml configuration file
-    Init {
-        /// Path to create the submission.yml config file
+    /// Check a submission.yml for mistakes without contacting the server
+    Validate {
+        /// Path to the submission.yml config file
         #[arg(long, default_value = "submission.yml")]
         config: String,
+    },
 
-        /// API key for authentication
-        #[arg(long)]
-        api_key: Option<String>,
+    /// Manage the submission.yml configuration file
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
 
-        /// Competition ID
-        #[arg(long)]
-        competition_id: Option<String>,
+    /// Show recent submissions from the local history, or manage it
+    History {
+        /// Number of most recent records to show
+        #[arg(short = 'n', long, default_value_t = 10)]
+        limit: usize,
+
+        #[command(subcommand)]
+        action: Option<HistoryCommand>,
     },
 
-    /// Check for updates and install the latest version
-    Update {
-        /// Force update without confirmation
-        #[arg(long)]
-        force: bool,
+    /// Inspect or clear cached data
+    Cache {
+        #[command(subcommand)]
+        action: CacheCommand,
     },
-}
 
-/// Load the configuration file or create a default one if it doesn't exist
-fn load_config(config_path: &str) -> Result<SubmissionConfig> {
-    let config_file = PathBuf::from(config_path);
-    
+    /// Download a stored submission from the server for local verification
+    Fetch {
+        /// Path to the submission.yml config file
+        #[arg(long, default_value = "submission.yml")]
+        config: String,
+
+        /// Submission ID to download
+        #[arg(long)]
+        id: Option<String>,
+
+        /// Download the most recent submission from history (default when no ID is given)
+        #[arg(long, conflicts_with = "id")]
+        last: bool,
+
+        /// Path to write the downloaded archive to (defaults to submission-<id>.zip)
+        #[arg(long)]
+        output: Option<String>,
+
+        /// API key for authentication (overrides config file)
+        #[arg(long)]
+        api_key: Option<String>,
+
+        /// Base URL for the server (overrides config file)
+        #[arg(long)]
+        server: Option<String>,
+
+        /// Named environment or profile from the config to overlay (defaults to $OPTIMUS_ENV)
+        #[arg(long = "env", visible_alias = "profile")]
+        environment: Option<String>,
+    },
+
+    /// Show remaining attempts, the required format and the last submission time without submitting
+    Status {
+        /// Path to the submission.yml config file
+        #[arg(long, default_value = "submission.yml")]
+        config: String,
+
+        /// Competition ID or alias (overrides config file; also accepted as --competition)
+        #[arg(long, visible_alias = "competition")]
+        competition_id: Option<String>,
+
+        /// Base URL for the server (overrides config file)
+        #[arg(long)]
+        server: Option<String>,
+
+        /// API key for authentication (overrides config file)
+        #[arg(long)]
+        api_key: Option<String>,
+
+        /// Named environment or profile from the config to overlay (defaults to $OPTIMUS_ENV)
+        #[arg(long = "env", visible_alias = "profile")]
+        environment: Option<String>,
+    },
+
+    /// Remove archives left in the temp directory by interrupted runs
+    Clean {
+        /// List leftover archives without removing them
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Directory to clean instead of $OPTIMUS_TMPDIR or the system temp dir
+        #[arg(long)]
+        temp_dir: Option<String>,
+    },
+
+    /// Show version and build details for bug reports
+    Version,
+
+    /// Check for updates and install the latest version
+    Update {
+        /// Force update without confirmation
+        #[arg(long)]
+        force: bool,
+
+        /// Release channel to update from: stable or prerelease (overrides config file)
+        #[arg(long, value_enum)]
+        channel: Option<UpdateChannel>,
+
+        /// Path to the submission.yml config file (read for `update_channel` if present)
+        #[arg(long, default_value = "submission.yml")]
+        config: String,
+    },
+}
+
+/// Arguments for `optimus prepare`
+#[derive(Args, Debug)]
+struct PrepareArgs {
+    /// Path to the submission.yml config file
+    #[arg(long, default_value = "submission.yml")]
+    config: String,
+
+    /// Competition ID or alias recorded with the submission (also accepted as --competition)
+    #[arg(long, visible_alias = "competition")]
+    competition_id: Option<String>,
+
+    /// Submission format to build (overrides config file)
+    #[arg(long)]
+    force_format: Option<String>,
+
+    /// Ask the server for approval and the required format first
+    #[arg(long)]
+    check: bool,
+
+    /// Base URL for the server used by --check (overrides config file)
+    #[arg(long)]
+    server: Option<String>,
+
+    /// Compression level (0-9, overrides config file)
+    #[arg(long)]
+    compression: Option<u8>,
+
+    /// Directory to write the archive and its metadata to
+    #[arg(long, default_value = "prepared")]
+    output: String,
+}
+
+/// Arguments for `optimus send`
+#[derive(Args, Debug)]
+struct SendArgs {
+    /// Path to the submission.yml config file (repeatable; later files override earlier ones)
+    #[arg(long, default_value = "submission.yml")]
+    config: Vec<String>,
+
+    /// Config file layered after --config files, ignored if missing (repeatable)
+    #[arg(long)]
+    config_optional: Vec<String>,
+
+    /// Competition ID or alias (overrides config file; also accepted as --competition)
+    #[arg(long, visible_alias = "competition")]
+    competition_id: Option<String>,
+
+    /// Read the competition ID (or alias) from this file; --competition-id takes precedence
+    #[arg(long)]
+    competition_file: Option<String>,
+
+    /// API key for authentication (overrides config file)
+    #[arg(long)]
+    api_key: Option<String>,
+
+    /// Base URL for the server (overrides config file)
+    #[arg(long)]
+    server: Option<String>,
+
+    /// Proxy URL for server requests, e.g. socks5://127.0.0.1:1080 (overrides config file)
+    #[arg(long)]
+    proxy: Option<String>,
+
+    /// Accept an invalid or self-signed server certificate (same as `allow_insecure_tls: true`)
+    #[arg(long)]
+    insecure: bool,
+
+    /// Seconds to wait for the server's check response (overrides `check_timeout_secs`)
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    check_timeout: Option<u64>,
+
+    /// Seconds the upload may take before it is abandoned (overrides `upload_timeout_secs`)
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    upload_timeout: Option<u64>,
+
+    /// Attempts for each network request, including the first (retries use exponential backoff)
+    #[arg(long, default_value_t = DEFAULT_REQUEST_ATTEMPTS, value_parser = clap::value_parser!(u32).range(1..))]
+    retries: u32,
+
+    /// Extra HTTP status codes to retry, comma-separated (adds to the `retry_on` setting)
+    #[arg(long, value_delimiter = ',', value_name = "CODES")]
+    retry_on: Vec<u16>,
+
+    /// Compression level (0-9, overrides config file)
+    #[arg(long)]
+    compression: Option<u8>,
+
+    /// Compression method: stored, deflate, bzip2 or zstd (overrides config file)
+    #[arg(long, value_name = "METHOD")]
+    compression_method: Option<String>,
+
+    /// Archive container to upload: zip or targz (overrides config file)
+    #[arg(long, value_enum)]
+    archive_format: Option<ArchiveFormat>,
+
+    /// Refuse to upload an archive larger than this, e.g. 50MB (overrides `max_archive_bytes`)
+    #[arg(long, value_name = "SIZE")]
+    max_size: Option<String>,
+
+    /// Skip server check and force a specific format (repo or py) (overrides config file)
+    #[arg(long)]
+    force_format: Option<String>,
+
+    /// Auto-confirm submission without prompting (overrides config file)
+    #[arg(long)]
+    auto_confirm: bool,
+
+    /// Number of threads reading files while the archive is compressed
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    read_threads: u16,
+
+    /// Chunks sent at once when the server accepts chunked uploads
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..=16))]
+    upload_concurrency: u16,
+
+    /// Print the JSON manifest (paths, sizes, hashes) of what would be archived and exit
+    #[arg(long)]
+    print_manifest_only: bool,
+
+    /// List the files that would be archived or left out, with sizes, without building or uploading
+    #[arg(long, conflicts_with_all = ["print_manifest_only", "prepared"])]
+    dry_run: bool,
+
+    /// Write the archive to this path (or into this directory) instead of uploading it
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["print_manifest_only", "dry_run", "prepared"])]
+    output: Option<String>,
+
+    /// Remove this leading directory from archive entry paths (e.g. `submission`)
+    #[arg(long)]
+    strip_prefix: Option<String>,
+
+    /// Nest all archive entries under this directory (applied after --strip-prefix)
+    #[arg(long)]
+    add_prefix: Option<String>,
+
+    /// Named environment or profile from the config to overlay (defaults to $OPTIMUS_ENV)
+    #[arg(long = "env", visible_alias = "profile")]
+    environment: Option<String>,
+
+    /// Include the config file itself in the archive (it is skipped by default)
+    #[arg(long, alias = "no-submission-yml-exclude")]
+    include_config: bool,
+
+    /// Exit non-zero if any warnings were emitted (after the run, or before-upload)
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "after")]
+    fail_on_warnings: Option<WarningPolicy>,
+
+    /// Upload only files changed since the server's base submission, when it offers one
+    #[arg(long)]
+    delta: bool,
+
+    /// Allow submitting to the default localhost server without confirmation (required in CI)
+    #[arg(long)]
+    allow_localhost: bool,
+
+    /// Pick compression for archive size, build speed, or a balance (overrides --compression and --compression-method)
+    #[arg(long, value_enum)]
+    optimize_for: Option<OptimizeFor>,
+
+    /// Archive this pre-staged directory instead of the current one (config is still read from here)
+    #[arg(long)]
+    from_archive_dir: Option<String>,
+
+    /// Only include entries up to this depth (1 = files and directories at the root)
+    #[arg(long)]
+    max_depth: Option<usize>,
+
+    /// Skip the submission if the last successful one for this competition was more recent (e.g. 30m, 1h)
+    #[arg(long)]
+    min_interval: Option<String>,
+
+    /// Directory for the intermediate archive (defaults to $OPTIMUS_TMPDIR, then the system temp dir)
+    #[arg(long)]
+    temp_dir: Option<String>,
+
+    /// Leave out files ignored by .gitignore (same as `preferences.respect_gitignore`)
+    #[arg(long)]
+    gitignore: bool,
+
+    /// Only include files modified within this window (e.g. 7d, 12h)
+    #[arg(long)]
+    modified_within: Option<String>,
+
+    /// Compute the archive checksum while uploading instead of in a separate pass first
+    #[arg(long)]
+    parallel_hash: bool,
+
+    /// Review files changed since the last submission before uploading
+    #[arg(long)]
+    review: bool,
+
+    /// Checksum algorithm for the uploaded archive (overrides config file)
+    #[arg(long, value_enum)]
+    hash_algo: Option<HashAlgo>,
+
+    /// Ignore exclusions enforced by the server, fresh or cached (for debugging)
+    #[arg(long)]
+    no_server_excludes: bool,
+
+    /// MIME type declared for the uploaded archive (defaults to one matching the archive format)
+    #[arg(long)]
+    content_type: Option<String>,
+
+    /// Name for this submission (e.g. v1, final), sent to the server and kept in history
+    #[arg(long)]
+    label: Option<String>,
+
+    /// Replace an existing submission with the same --label
+    #[arg(long, requires = "label")]
+    overwrite_label: bool,
+
+    /// Short note describing this submission, sent to the server and kept in history
+    #[arg(long)]
+    note: Option<String>,
+
+    /// Also leave out paths matching this glob, on top of the config's `exclude` (repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    exclude: Vec<String>,
+
+    /// Also archive files matching this glob, adding to the config's `include` (repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    include: Vec<String>,
+
+    /// Leave out directories that contain no files after filtering
+    #[arg(long)]
+    exclude_empty_dirs: bool,
+
+    /// Abort if the directory is a git work tree with uncommitted or untracked changes
+    #[arg(long)]
+    require_clean_git: bool,
+
+    /// Submit even if the git tree is dirty, overriding require_clean_git
+    #[arg(long)]
+    allow_dirty: bool,
+
+    /// Abort instead of warning when the archive contains files that look like secrets
+    #[arg(long)]
+    strict_secrets: bool,
+
+    /// Skip the server health check done before building the archive
+    #[arg(long)]
+    no_preflight: bool,
+
+    /// Keep the temporary archive after uploading and print where it is
+    #[arg(long)]
+    no_cleanup: bool,
+
+    /// Compare the collected files to an expected manifest (YAML/JSON with `required` and `allowed` paths)
+    #[arg(long, value_name = "FILE")]
+    manifest_against: Option<String>,
+
+    /// Report --manifest-against mismatches without aborting
+    #[arg(long, requires = "manifest_against")]
+    warn_only: bool,
+
+    /// Upload a submission staged by `optimus prepare` in this directory, re-verifying its checksum
+    #[arg(long, value_name = "DIR", conflicts_with_all = [
+        "print_manifest_only", "delta", "review", "parallel_hash", "manifest_against", "content_type"
+    ])]
+    prepared: Option<String>,
+
+    /// On success, print only a one-line result instead of progress messages and the summary
+    #[arg(long)]
+    quiet_success: bool,
+
+    /// Like --quiet-success, but print nothing at all on success
+    #[arg(long)]
+    silent: bool,
+
+    /// Print an end-of-run summary (default when running in a terminal)
+    #[arg(long, overrides_with = "no_summary")]
+    summary: bool,
+
+    /// Don't print the end-of-run summary
+    #[arg(long)]
+    no_summary: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Open the config file in $VISUAL/$EDITOR and validate it after saving
+    Edit {
+        /// Path to the submission.yml config file (searched for in parent directories)
+        #[arg(long, default_value = "submission.yml")]
+        config: String,
+    },
+
+    /// List the competition aliases defined in the config
+    Aliases {
+        /// Path to the submission.yml config file
+        #[arg(long, default_value = "submission.yml")]
+        config: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheCommand {
+    /// Show where the cache lives and how much space each part uses
+    Info,
+
+    /// Remove cached data
+    Clear {
+        /// Which cache to remove
+        #[arg(long, value_enum, default_value = "all")]
+        what: CacheTarget,
+    },
+}
+
+/// Cached or remembered data that can be inspected and cleared
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum CacheTarget {
+    /// Server check responses kept for offline use
+    Check,
+    /// The last submission's file list (and archive, after --review) used by --review
+    Submissions,
+    /// The remembered upload rate and last competition picked per server, in ~/.optimus
+    State,
+    /// Everything above
+    All,
+}
+
+impl CacheTarget {
+    /// The individual caches this target covers
+    fn kinds(self) -> &'static [CacheTarget] {
+        match self {
+            CacheTarget::All => &[CacheTarget::Check, CacheTarget::Submissions, CacheTarget::State],
+            CacheTarget::Check => &[CacheTarget::Check],
+            CacheTarget::Submissions => &[CacheTarget::Submissions],
+            CacheTarget::State => &[CacheTarget::State],
+        }
+    }
+    
+    /// Name shown by `optimus cache info`, and the subdirectory of the cache directory
+    /// for caches kept there
+    fn dir_name(self) -> &'static str {
+        match self {
+            CacheTarget::Check => "check",
+            CacheTarget::Submissions => "submissions",
+            CacheTarget::State => "state",
+            CacheTarget::All => "",
+        }
+    }
+    
+    /// Files and directories holding this cache
+    fn paths(self) -> Result<Vec<PathBuf>> {
+        match self {
+            CacheTarget::State => Ok(vec![upload_rate_path()?, last_competition_path()?]),
+            CacheTarget::All => {
+                let mut paths = Vec::new();
+                for kind in self.kinds() {
+                    paths.extend(kind.paths()?);
+                }
+                Ok(paths)
+            },
+            kind => Ok(vec![cache_dir()?.join(kind.dir_name())]),
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum HistoryCommand {
+    /// Remove old records from the history file
+    Prune {
+        /// Keep only the most recent N records (defaults to history_limit from the config)
+        #[arg(long)]
+        keep: Option<usize>,
+
+        /// Remove records older than this (e.g. 30d, 12h)
+        #[arg(long)]
+        older_than: Option<String>,
+
+        /// Path to the submission.yml config file to read history_limit from
+        #[arg(long, default_value = "submission.yml")]
+        config: String,
+    },
+}
+
+/// Find a relative config path in the current directory or the nearest parent that has it
+fn find_config_upwards(config_path: &str) -> Option<PathBuf> {
+    let path = Path::new(config_path);
+    if path.is_absolute() || path.exists() {
+        return path.exists().then(|| path.to_path_buf());
+    }
+    
+    let current_dir = env::current_dir().ok()?;
+    current_dir.ancestors()
+        .map(|dir| dir.join(path))
+        .find(|candidate| candidate.exists())
+}
+
+/// The user's preferred editor command, split into program and arguments
+fn editor_command() -> Vec<String> {
+    let editor = env::var("VISUAL").ok()
+        .filter(|e| !e.trim().is_empty())
+        .or_else(|| env::var("EDITOR").ok().filter(|e| !e.trim().is_empty()))
+        .unwrap_or_else(|| if cfg!(windows) { "notepad".to_string() } else { "vi".to_string() });
+    
+    editor.split_whitespace().map(|part| part.to_string()).collect()
+}
+
+/// Open the config in an editor, creating it first if needed, then re-validate it
+fn edit_config(config_path: &str) -> Result<()> {
+    let path = match find_config_upwards(config_path) {
+        Some(path) => path,
+        None => {
+            let create = Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!("Config file '{}' not found. Create it?", config_path))
+                .default(true)
+                .interact()?;
+            
+            if !create {
+                println!("❌ Config edit aborted.");
+                return Ok(());
+            }
+            
+            create_config_file(config_path, None, None)?;
+            PathBuf::from(config_path)
+        }
+    };
+    
+    let editor = editor_command();
+    let (program, args) = editor.split_first()
+        .ok_or_else(|| anyhow::anyhow!("No editor configured. Set $VISUAL or $EDITOR."))?;
+    
+    println!("📝 Opening {} with {}", path.display(), program);
+    
+    let status = Command::new(program)
+        .args(args)
+        .arg(&path)
+        .status()
+        .with_context(|| format!("Failed to launch editor '{}'", program))?;
+    
+    if !status.success() {
+        return Err(anyhow::anyhow!(
+            "Editor exited with an error. Exit code: {}",
+            status.code().unwrap_or(-1)
+        ));
+    }
+    
+    // Make sure the saved file still parses
+    load_config(&path.to_string_lossy())
+        .with_context(|| format!("'{}' is no longer a valid config file", path.display()))?;
+    
+    println!("✅ Config file is valid: {}", path.display());
+    
+    Ok(())
+}
+
+/// Load the configuration file or create a default one if it doesn't exist
+fn load_config(config_path: &str) -> Result<SubmissionConfig> {
+    config_from_value(read_config_value(config_path)?)
+}
+
+/// Syntax of a config file
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum ConfigFormat {
+    Yaml,
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// The format implied by a config file's extension, if it is one we recognize
+    fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension().and_then(|ext| ext.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("yml") | Some("yaml") => Some(ConfigFormat::Yaml),
+            Some("toml") => Some(ConfigFormat::Toml),
+            Some("json") => Some(ConfigFormat::Json),
+            _ => None,
+        }
+    }
+    
+    /// `--config-format` if given, otherwise the format of the file's extension
+    fn requested(path: &Path) -> Option<Self> {
+        CONFIG_FORMAT.lock().ok()
+            .and_then(|format| *format)
+            .or_else(|| ConfigFormat::from_extension(path))
+    }
+    
+    fn as_str(self) -> &'static str {
+        match self {
+            ConfigFormat::Yaml => "YAML",
+            ConfigFormat::Toml => "TOML",
+            ConfigFormat::Json => "JSON",
+        }
+    }
+}
+
+/// `--config` value that reads the config from stdin instead of a file
+const STDIN_CONFIG: &str = "-";
+
+/// Whether a config path names an existing file, or stdin
+fn config_exists(config_path: &str) -> bool {
+    config_path == STDIN_CONFIG || Path::new(config_path).exists()
+}
+
+/// The config piped to stdin, read once and shared by every `--config -`
+fn stdin_config() -> Result<&'static str> {
+    static STDIN_TEXT: OnceLock<String> = OnceLock::new();
+    
+    if let Some(text) = STDIN_TEXT.get() {
+        return Ok(text.as_str());
+    }
+    let mut text = String::new();
+    std::io::stdin().read_to_string(&mut text)
+        .context("Failed to read the config from stdin")?;
+    Ok(STDIN_TEXT.get_or_init(|| text).as_str())
+}
+
+/// Read a config file as an untyped YAML value, whatever syntax it is written in.
+///
+/// TOML and JSON files are converted so layering, interpolation and `OPTIMUS_*`
+/// overrides work the same for every format.
+fn read_config_value(config_path: &str) -> Result<serde_yaml::Value> {
+    // Piped configs have no extension, so they are YAML unless --config-format says otherwise
+    if config_path == STDIN_CONFIG {
+        let format = ConfigFormat::requested(Path::new("")).unwrap_or(ConfigFormat::Yaml);
+        return parse_config_text(stdin_config()?, format)
+            .with_context(|| format!("Failed to parse the config from stdin as {}", format.as_str()));
+    }
+    
+    let config_file = PathBuf::from(config_path);
+    
     if !config_file.exists() {
         return Err(anyhow::anyhow!(
             "Configuration file '{}' not found. You can create one with `optimus init`.", 
             config_path
         ));
     }
-    
-    // Read the config file
-    let file = File::open(config_file)?;
-    let config: SubmissionConfig = serde_yaml::from_reader(file)?;
-    
-    Ok(config)
-}
+    
+    let format = ConfigFormat::requested(&config_file).unwrap_or(ConfigFormat::Yaml);
+    let text = std::fs::read_to_string(&config_file)
+        .with_context(|| format!("Failed to read config file '{}'", config_path))?;
+    
+    parse_config_text(&text, format)
+        .with_context(|| format!("Failed to parse config file '{}' as {}", config_path, format.as_str()))
+}
+
+/// Parse config text in the given syntax into an untyped YAML value
+fn parse_config_text(text: &str, format: ConfigFormat) -> Result<serde_yaml::Value> {
+    match format {
+        ConfigFormat::Yaml => Ok(serde_yaml::from_str(text)?),
+        ConfigFormat::Toml => Ok(toml::from_str(text)?),
+        ConfigFormat::Json => Ok(serde_json::from_str(text)?),
+    }
+}
+
+/// Scalar config fields that `OPTIMUS_<FIELD>` variables can set, and whether each is plain text
+const ENV_CONFIG_FIELDS: &[(&str, bool)] = &[
+    ("api_key", true),
+    ("competition_id", true),
+    ("format", true),
+    ("server_url", true),
+    ("compression_level", false),
+    ("compression_method", true),
+    ("archive_format", true),
+    ("max_archive_bytes", false),
+    ("pool_idle_timeout_secs", false),
+    ("check_timeout_secs", false),
+    ("upload_timeout_secs", false),
+    ("proxy", true),
+    ("allow_insecure_tls", false),
+    ("update_channel", true),
+    ("api_prefix", true),
+    ("health_path", true),
+    ("check_path", true),
+    ("submit_path", true),
+    ("archive_name", true),
+    ("min_interval", true),
+    ("cancel_on_failure", false),
+    ("require_clean_git", false),
+    ("require_git_repo", false),
+    ("include_empty_dirs", false),
+    ("follow_symlinks", false),
+    ("auth_scheme", true),
+    ("hash_algo", true),
+    ("username", true),
+    ("auth_header", true),
+];
+
+/// Override scalar fields from `OPTIMUS_<FIELD>` environment variables (e.g. `OPTIMUS_SERVER_URL`).
+///
+/// Precedence is command-line flag > environment > config file > default; flags are
+/// applied later by each command.
+fn apply_env_config(value: &mut serde_yaml::Value) -> Result<()> {
+    // An empty config file parses as null
+    if value.is_null() {
+        *value = serde_yaml::Value::Mapping(serde_yaml::Mapping::new());
+    }
+    let mapping = value.as_mapping_mut()
+        .context("Configuration must be a mapping of settings")?;
+    
+    for (field, is_text) in ENV_CONFIG_FIELDS {
+        let var = format!("OPTIMUS_{}", field.to_uppercase());
+        let Ok(raw) = env::var(&var) else {
+            continue;
+        };
+        
+        // Text stays text even if it looks like a number (e.g. a numeric competition ID)
+        let parsed = if *is_text {
+            serde_yaml::Value::String(raw)
+        } else {
+            serde_yaml::from_str(&raw).with_context(|| format!("Invalid value '{}' in {}", raw, var))?
+        };
+        mapping.insert(serde_yaml::Value::String(field.to_string()), parsed);
+    }
+    
+    Ok(())
+}
+
+/// Expand `${VAR}` and `$VAR` references to environment variables; `$$` is a literal `$`
+fn interpolate_env(text: &str, field: &str) -> Result<String> {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    
+    while let Some(pos) = rest.find('$') {
+        expanded.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+        
+        let (name, remainder) = if let Some(braced) = after.strip_prefix('{') {
+            let end = braced.find('}').with_context(|| format!(
+                "Unterminated '${{' in config field '{}'",
+                field
+            ))?;
+            (&braced[..end], &braced[end + 1..])
+        } else if let Some(remainder) = after.strip_prefix('$') {
+            expanded.push('$');
+            rest = remainder;
+            continue;
+        } else {
+            let end = after
+                .char_indices()
+                .find(|(i, c)| !(c.is_ascii_alphabetic() || *c == '_' || (*i > 0 && c.is_ascii_digit())))
+                .map(|(i, _)| i)
+                .unwrap_or(after.len());
+            (&after[..end], &after[end..])
+        };
+        
+        // A lone '$' (e.g. in a password) is kept as written
+        if name.is_empty() {
+            expanded.push('$');
+            rest = after;
+            continue;
+        }
+        
+        let value = env::var(name).map_err(|_| anyhow::anyhow!(
+            "Config field '{}' references ${}, which is not set",
+            field,
+            name
+        ))?;
+        expanded.push_str(&value);
+        rest = remainder;
+    }
+    expanded.push_str(rest);
+    
+    Ok(expanded)
+}
+
+/// Interpolate environment variables into every string in the config.
+///
+/// Named environments are left alone so an unused one can reference variables that
+/// are not set; they have `api_key_env` for reading secrets instead.
+fn interpolate_config_value(value: &mut serde_yaml::Value, field: &str) -> Result<()> {
+    match value {
+        serde_yaml::Value::String(text) => *text = interpolate_env(text, field)?,
+        serde_yaml::Value::Sequence(items) => {
+            for item in items {
+                interpolate_config_value(item, field)?;
+            }
+        },
+        serde_yaml::Value::Mapping(mapping) => {
+            for (key, item) in mapping.iter_mut() {
+                let key = key.as_str().unwrap_or_default();
+                if field.is_empty() && (key == "environments" || key == "profiles") {
+                    continue;
+                }
+                let path = if field.is_empty() { key.to_string() } else { format!("{}.{}", field, key) };
+                interpolate_config_value(item, &path)?;
+            }
+        },
+        _ => {},
+    }
+    
+    Ok(())
+}
+
+/// Deserialize a config, noting which optional keys were actually present
+fn config_from_value(mut value: serde_yaml::Value) -> Result<SubmissionConfig> {
+    interpolate_config_value(&mut value, "")?;
+    apply_env_config(&mut value)?;
+    let server_url_set = value.get("server_url").is_some();
+    
+    let mut config: SubmissionConfig = serde_yaml::from_value(value)?;
+    config.server_url_set = server_url_set;
+    validate_retry_on(&config.retry_on)?;
+    if config.check_timeout_secs == 0 || config.upload_timeout_secs == 0 {
+        return Err(anyhow::anyhow!("check_timeout_secs and upload_timeout_secs must be at least 1"));
+    }
+    if let Some(method) = &config.compression_method {
+        parse_compression_method(method)?;
+    }
+    
+    Ok(config)
+}
+
+/// Deep-merge `overlay` into `base`: mappings merge key by key, lists are concatenated,
+/// and any other value in the overlay replaces the base value
+fn merge_config_values(base: &mut serde_yaml::Value, overlay: serde_yaml::Value) {
+    match (base, overlay) {
+        (serde_yaml::Value::Mapping(base_map), serde_yaml::Value::Mapping(overlay_map)) => {
+            for (key, value) in overlay_map {
+                match base_map.get_mut(&key) {
+                    Some(existing) => merge_config_values(existing, value),
+                    None => {
+                        base_map.insert(key, value);
+                    },
+                }
+            }
+        },
+        (serde_yaml::Value::Sequence(base_seq), serde_yaml::Value::Sequence(overlay_seq)) => {
+            base_seq.extend(overlay_seq);
+        },
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Fail early when a server command has no API key from `--api-key`, `OPTIMUS_API_KEY` or the config
+fn ensure_api_key(config: &SubmissionConfig) -> Result<()> {
+    if config.api_key.is_empty() && config.auth_scheme != AuthScheme::None {
+        return Err(anyhow::anyhow!(
+            "No API key provided. Pass --api-key, set OPTIMUS_API_KEY, or create a config file with `optimus init`."
+        ));
+    }
+    
+    Ok(())
+}
+
+/// Config file read when `--config` isn't given
+const DEFAULT_CONFIG_PATH: &str = "submission.yml";
+
+/// Load several config files layered in order, later files overriding earlier ones.
+///
+/// Files in `optional_paths` are layered after the required ones and skipped if missing.
+/// A missing `config_paths` file is an error, except the default `submission.yml` on its
+/// own, so flags and environment variables can stand in for a config file.
+fn load_layered_config(config_paths: &[String], optional_paths: &[String]) -> Result<SubmissionConfig> {
+    let mut merged: Option<serde_yaml::Value> = None;
+    
+    let only_default = matches!(config_paths, [path] if path == DEFAULT_CONFIG_PATH);
+    if let Some(missing) = config_paths.iter().find(|path| !config_exists(path)).filter(|_| !only_default) {
+        return Err(anyhow::anyhow!("Config file '{}' not found", missing));
+    }
+    
+    let required = config_paths.iter().filter(|path| config_exists(path));
+    let optional = optional_paths.iter().filter(|path| config_exists(path));
+    for path in required.chain(optional) {
+        let value = read_config_value(path)?;
+        match &mut merged {
+            Some(base) => merge_config_values(base, value),
+            None => merged = Some(value),
+        }
+    }
+    
+    // With no file at all, run from flags and environment variables alone
+    let merged = match merged {
+        Some(value) => value,
+        None => {
+            status!("📄 No configuration file found; using command-line flags and environment");
+            serde_yaml::Value::Mapping(serde_yaml::Mapping::new())
+        }
+    };
+    config_from_value(merged)
+}
+
+/// Read a competition ID written to a file by an earlier pipeline stage
+fn read_competition_file(path: &str) -> Result<String> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read competition file '{}'", path))?;
+    let id = contents.trim();
+    if id.is_empty() {
+        return Err(anyhow::anyhow!("Competition file '{}' is empty", path));
+    }
+    Ok(id.to_string())
+}
+
+/// Resolve a competition alias from the config; anything else is taken as a raw ID
+fn resolve_competition_alias(config: &SubmissionConfig, name: &str) -> String {
+    match config.aliases.get(name) {
+        Some(id) => {
+            status!("🔖 Competition alias '{}' resolves to {}", name, id);
+            id.clone()
+        }
+        None => name.to_string(),
+    }
+}
+
+/// Print the configured competition aliases
+fn list_aliases(config: &SubmissionConfig) {
+    if config.aliases.is_empty() {
+        println!("No competition aliases defined. Add an `aliases` map to the config.");
+        return;
+    }
+    
+    let width = config.aliases.keys().map(|name| name.len()).max().unwrap_or(0);
+    println!("🔖 Competition aliases:");
+    for (name, id) in &config.aliases {
+        println!("   {:<width$}  {}", name, id, width = width);
+    }
+}
+
+/// A competition the user can submit to, as listed by `GET /competitions`
+#[derive(Deserialize, Debug)]
+struct CompetitionSummary {
+    id: String,
+    #[serde(default)]
+    name: Option<String>,
+}
+
+/// Location of the last competition picked for each server
+fn last_competition_path() -> Result<PathBuf> {
+    let home = dirs::home_dir().context("Failed to locate home directory")?;
+    Ok(home.join(".optimus").join("last_competition.json"))
+}
+
+/// Last competition picked per server URL
+fn read_last_competitions() -> BTreeMap<String, String> {
+    last_competition_path().ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// Let the user choose one of their competitions, pre-selecting the last one used on this server.
+///
+/// Returns `None` (submitting without a competition, as before) when the server doesn't list any.
+fn select_competition(client: &Client, server_url: &str, config: &SubmissionConfig) -> Result<Option<String>> {
+    let request = client.get(api_url(server_url, config, "/competitions")?)
+        .timeout(Duration::from_secs(config.check_timeout_secs));
+    let response = authorize(request, config)?.send()?;
+    if !response.status().is_success() {
+        return Ok(None);
+    }
+    
+    let competitions: Vec<CompetitionSummary> = response.json()?;
+    if competitions.is_empty() {
+        return Ok(None);
+    }
+    
+    let mut remembered = read_last_competitions();
+    let default = remembered.get(server_url)
+        .and_then(|last| competitions.iter().position(|c| &c.id == last))
+        .unwrap_or(0);
+    let items: Vec<String> = competitions.iter()
+        .map(|c| match &c.name {
+            Some(name) => format!("{} ({})", name, c.id),
+            None => c.id.clone(),
+        })
+        .collect();
+    
+    let choice = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("No competition configured. Choose one")
+        .items(&items)
+        .default(default)
+        .interact()?;
+    let id = competitions[choice].id.clone();
+    
+    // Remembering is a convenience, so a failure only warns
+    remembered.insert(server_url.to_string(), id.clone());
+    let saved = last_competition_path().and_then(|path| {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(&remembered)?)?;
+        Ok(())
+    });
+    if let Err(err) = saved {
+        warn(format!("Failed to remember competition choice: {}", err));
+    }
+    
+    Ok(Some(id))
+}
+
+/// Overlay the selected environment (from `--env` or `OPTIMUS_ENV`) onto the config
+fn apply_environment(config: &mut SubmissionConfig, name: Option<&str>) -> Result<()> {
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => match env::var("OPTIMUS_ENV") {
+            Ok(name) if !name.is_empty() => name,
+            _ => return Ok(()),
+        },
+    };
+    
+    let overlay = match config.environments.get(&name) {
+        Some(overlay) => overlay.clone(),
+        None => {
+            let mut valid: Vec<&str> = config.environments.keys().map(|k| k.as_str()).collect();
+            valid.sort();
+            return Err(anyhow::anyhow!(
+                "Unknown environment '{}'. Valid environments: {}",
+                name,
+                if valid.is_empty() { "(none defined)".to_string() } else { valid.join(", ") }
+            ));
+        }
+    };
+    
+    // OPTIMUS_<FIELD> variables outrank the overlay, just as they outrank the base file
+    let from_env = |field: &str| env::var(format!("OPTIMUS_{}", field.to_uppercase())).is_ok();
+    
+    if let Some(server_url) = overlay.server_url.filter(|_| !from_env("server_url")) {
+        config.server_url = server_url;
+        config.server_url_set = true;
+    }
+    
+    let key_from_env = from_env("api_key");
+    
+    if let Some(api_key) = overlay.api_key.filter(|_| !key_from_env) {
+        config.api_key = api_key;
+    }
+    
+    if let Some(var) = overlay.api_key_env.filter(|_| !key_from_env) {
+        config.api_key = env::var(&var).with_context(|| format!(
+            "Environment '{}' reads its API key from ${}, which is not set",
+            name,
+            var
+        ))?;
+    }
+    
+    if let Some(competition_id) = overlay.competition_id.filter(|_| !from_env("competition_id")) {
+        config.competition_id = Some(competition_id);
+    }
+    
+    if let Some(format) = overlay.format.filter(|_| !from_env("format")) {
+        validate_format(&format)?;
+        config.format = Some(format);
+    }
+    
+    status!("🌐 Using environment: {}", name);
+    
+    Ok(())
+}
+
+/// Whether the process appears to be running under CI
+fn is_ci() -> bool {
+    env::var("CI")
+        .map(|value| !value.is_empty() && value != "false" && value != "0")
+        .unwrap_or(false)
+}
+
+/// Whether the submission falls back to the built-in default server without the user choosing it
+fn needs_default_server_guard(server_url: &str, explicit: bool, allow_localhost: bool) -> bool {
+    !explicit && !allow_localhost && server_url == default_server_url()
+}
+
+/// Guard against submitting to the built-in localhost default by accident.
+///
+/// Returns whether to proceed. An explicitly configured server (even localhost) is trusted.
+fn guard_default_server(server_url: &str, explicit: bool, allow_localhost: bool, auto_confirm: bool) -> Result<bool> {
+    if !needs_default_server_guard(server_url, explicit, allow_localhost) {
+        return Ok(true);
+    }
+    
+    if is_ci() {
+        return Err(anyhow::anyhow!(
+            "No server configured, refusing to submit to the default {} in CI. Set server_url or pass --allow-localhost.",
+            server_url
+        ));
+    }
+    
+    warn(format!("No server configured, submitting to the default {}", server_url));
+    
+    if auto_confirm {
+        return Ok(true);
+    }
+    
+    let proceed = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt("Submit to the local default server anyway?")
+        .default(false)
+        .interact()?;
+    
+    Ok(proceed)
+}
+
+/// Create a new configuration file
+fn create_config_file(config_path: &str, api_key: Option<String>, competition_id: Option<String>) -> Result<()> {
+    let config_file = PathBuf::from(config_path);
+    
+    if config_file.exists() {
+        let overwrite = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Config file '{}' already exists. Overwrite?", config_path))
+            .default(false)
+            .interact()?;
+        
+        if !overwrite {
+            println!("❌ Config creation aborted.");
+            return Ok(());
+        }
+    }
+    
+    // Create a default config
+    let config = SubmissionConfig {
+        api_key: api_key.unwrap_or_else(|| "your-api-key-here".to_string()),
+        competition_id,
+        format: None,
+        server_url: default_server_url(),
+        server_url_set: false,
+        compression_level: default_compression_level(),
+        compression_method: None,
+        archive_format: ArchiveFormat::default(),
+        exclude: vec![
+            ".git".to_string(),
+            ".DS_Store".to_string(), 
+            "node_modules".to_string(),
+            "target".to_string(),
+            ".env".to_string(),
+            "venv".to_string(),
+        ],
+        include: Vec::new(),
+        secret_patterns: Vec::new(),
+        max_archive_bytes: None,
+        pool_idle_timeout_secs: default_pool_idle_timeout_secs(),
+        check_timeout_secs: default_check_timeout_secs(),
+        upload_timeout_secs: default_upload_timeout_secs(),
+        proxy: None,
+        allow_insecure_tls: false,
+        update_channel: UpdateChannel::default(),
+        api_prefix: None,
+        health_path: default_health_path(),
+        check_path: default_check_path(),
+        submit_path: default_submit_path(),
+        retry_on: Vec::new(),
+        archive_name: None,
+        min_interval: None,
+        cancel_on_failure: false,
+        require_clean_git: false,
+        require_git_repo: false,
+        include_empty_dirs: true,
+        follow_symlinks: false,
+        environments: HashMap::new(),
+        aliases: BTreeMap::new(),
+        auth_scheme: AuthScheme::default(),
+        hash_algo: HashAlgo::default(),
+        username: None,
+        auth_header: None,
+        preferences: Preferences {
+            auto_confirm: false,
+            save_history: true,
+            estimate_upload_time: false,
+            history_limit: default_history_limit(),
+            respect_gitignore: false,
+            keep_archive: false,
+        },
+    };
+    
+    // Write the config in the format its name asks for
+    let format = ConfigFormat::requested(&config_file).unwrap_or_else(|| {
+        warn(format!("Unrecognized config extension for '{}', writing YAML", config_path));
+        ConfigFormat::Yaml
+    });
+    let contents = match format {
+        ConfigFormat::Yaml => serde_yaml::to_string(&config)?,
+        ConfigFormat::Toml => toml::to_string_pretty(&config)?,
+        ConfigFormat::Json => serde_json::to_string_pretty(&config)? + "\n",
+    };
+    std::fs::write(&config_file, contents)?;
+    
+    println!("✅ Created configuration file: {}", config_path);
+    println!("   Please edit it to set your API key and other preferences.");
+    
+    Ok(())
+}
+
+/// User agent sent with every request, e.g. `optimus/0.1.0`
+const USER_AGENT: &str = concat!("optimus/", env!("CARGO_PKG_VERSION"));
+
+/// How long any request may take to establish a connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Start every HTTP client the same way, with the versioned user agent and connect timeout.
+///
+/// An explicit proxy wins, otherwise reqwest uses `HTTPS_PROXY`, `HTTP_PROXY` or `ALL_PROXY`
+/// from the environment. `NO_PROXY` applies to both.
+fn client_builder(proxy: Option<&str>) -> Result<ClientBuilder> {
+    let builder = Client::builder()
+        .user_agent(USER_AGENT)
+        .connect_timeout(CONNECT_TIMEOUT);
+    match proxy {
+        Some(url) => Ok(builder.proxy(parse_proxy(url)?)),
+        None => Ok(builder),
+    }
+}
+
+/// Build the HTTP client shared by all server requests so connections are reused
+fn build_client(config: &SubmissionConfig) -> Result<Client> {
+    let mut builder = client_builder(config.proxy.as_deref())?
+        .pool_idle_timeout(Duration::from_secs(config.pool_idle_timeout_secs));
+    if config.allow_insecure_tls {
+        warn("TLS certificate verification is OFF: the server's identity is not checked and traffic could be intercepted");
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Client for GitHub release checks and downloads, honoring `OPTIMUS_PROXY`
+fn update_client() -> Result<Client> {
+    let proxy = env::var("OPTIMUS_PROXY").ok();
+    Ok(client_builder(proxy.as_deref())?.build()?)
+}
+
+/// Parse a proxy URL, accepting HTTP(S) and SOCKS5 proxies.
+///
+/// `socks5h://` resolves host names through the proxy, `socks5://` resolves them locally.
+fn parse_proxy(url: &str) -> Result<reqwest::Proxy> {
+    let scheme = url.split_once("://").map(|(scheme, _)| scheme.to_ascii_lowercase());
+    match scheme.as_deref() {
+        Some("http" | "https") => {}
+        Some("socks5" | "socks5h") if cfg!(feature = "socks") => {}
+        Some("socks5" | "socks5h") => return Err(anyhow::anyhow!(
+            "This build of optimus doesn't support SOCKS proxies (see `optimus version`)"
+        )),
+        _ => return Err(anyhow::anyhow!(
+            "Unsupported proxy '{}'. Expected an http://, https://, socks5:// or socks5h:// URL",
+            url
+        )),
+    }
+    
+    let proxy = reqwest::Proxy::all(url).with_context(|| format!("Invalid proxy URL '{}'", url))?;
+    Ok(proxy.no_proxy(reqwest::NoProxy::from_env()))
+}
+
+/// Lint a config file, printing a checklist and failing if any check does not pass
+fn run_validate(config_path: &str) -> Result<()> {
+    let config = load_config(config_path)
+        .with_context(|| format!("'{}' is not a valid config file", config_path))?;
+    println!("🔎 Validating {}", config_path);
+    
+    let api_key = if config.api_key.is_empty() {
+        Err("is empty".to_string())
+    } else if config.api_key == "your-api-key-here" {
+        Err("is still the placeholder from `optimus init`".to_string())
+    } else {
+        Ok(())
+    };
+    let compression = if config.compression_level <= 9 {
+        Ok(())
+    } else {
+        Err(format!("is {}, expected 0-9", config.compression_level))
+    };
+    let server_url = match reqwest::Url::parse(&config.server_url) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => Ok(()),
+        Ok(url) => Err(format!("uses unsupported scheme '{}'", url.scheme())),
+        Err(e) => Err(format!("'{}' is not a valid URL: {}", config.server_url, e)),
+    };
+    let format = match config.format.as_deref() {
+        Some(format) => validate_format(format).map_err(|e| e.to_string()),
+        None => Ok(()),
+    };
+    let endpoint = |path: &str| api_url(&config.server_url, &config, path)
+        .map(|_| ())
+        .map_err(|_| format!("'{}' doesn't form a valid URL with server_url", path));
+    
+    let checks = [
+        ("api_key", api_key),
+        ("compression_level", compression),
+        ("server_url", server_url),
+        ("health_path", endpoint(&config.health_path)),
+        ("check_path", endpoint(&config.check_path)),
+        ("submit_path", endpoint(&config.submit_path)),
+        ("format", format),
+    ];
+    let mut failures = 0;
+    for (field, result) in &checks {
+        match result {
+            Ok(()) => println!("  ✅ {}", field),
+            Err(problem) => {
+                failures += 1;
+                println!("  ❌ {} {}", field, problem);
+            },
+        }
+    }
+    
+    if failures > 0 {
+        return Err(anyhow::anyhow!("{} of {} checks failed", failures, checks.len()));
+    }
+    
+    println!("✅ Config file is valid: {}", config_path);
+    
+    Ok(())
+}
+
+/// Validate format is either "repo" or "py"
+fn validate_format(format: &str) -> Result<()> {
+    if format != "repo" && format != "py" {
+        return Err(anyhow::anyhow!(
+            "Unsupported format: {}. Expected 'repo' or 'py'", 
+            format
+        ));
+    }
+    
+    Ok(())
+}
+
+/// Check an existing zip against a format's rules, returning every problem found.
+///
+/// Required files must be present at the archive root; with `strict`, the `py` format
+/// also rejects files that aren't Python sources or project files.
+fn verify_archive(zip_path: &Path, format: &str, strict: bool) -> Result<Vec<String>> {
+    let mut archive = zip::ZipArchive::new(File::open(zip_path)?)
+        .with_context(|| format!("'{}' is not a readable zip archive", zip_path.display()))?;
+    
+    let mut names = Vec::new();
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i)?;
+        if !entry.is_dir() {
+            names.push(entry.name().to_string());
+        }
+    }
+    
+    let mut problems = Vec::new();
+    
+    for required in required_files(format) {
+        if !names.iter().any(|name| name == required) {
+            problems.push(format!("Missing required file: {}", required));
+        }
+    }
+    
+    if strict && format == "py" {
+        for name in &names {
+            if !PYTHON_FORMAT_PATTERNS.iter().any(|pattern| name.ends_with(pattern)) {
+                problems.push(format!("File not allowed in the py format: {}", name));
+            }
+        }
+    }
+    
+    Ok(problems)
+}
+
+/// Files a format expects at the root of a submission
+fn required_files(format: &str) -> &'static [&'static str] {
+    match format {
+        "py" => &["main.py", "requirements.txt"],
+        _ => &[],
+    }
+}
+
+/// Starter files scaffolded by `optimus template` for a format, as (path, contents)
+fn template_files(format: &str) -> Vec<(&'static str, &'static str)> {
+    match format {
+        "py" => vec![
+            ("main.py", "def main():\n    pass\n\n\nif __name__ == \"__main__\":\n    main()\n"),
+            ("requirements.txt", "# Add your Python dependencies here, one per line\n"),
+        ],
+        _ => vec![
+            ("README.md", "# Submission\n\nDescribe your solution here.\n"),
+        ],
+    }
+}
+
+/// Scaffold the starter files for a format plus a config file, prompting before overwriting
+fn scaffold_template(format: &str, config_path: &str) -> Result<()> {
+    validate_format(format)?;
+    
+    for (name, contents) in template_files(format) {
+        let path = PathBuf::from(name);
+        
+        if path.exists() {
+            let overwrite = Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!("'{}' already exists. Overwrite?", name))
+                .default(false)
+                .interact()?;
+            
+            if !overwrite {
+                println!("⏭️ Kept existing {}", name);
+                continue;
+            }
+        }
+        
+        std::fs::write(&path, contents)?;
+        println!("✅ Created {}", name);
+    }
+    
+    let required = required_files(format);
+    if !required.is_empty() {
+        println!("📋 The '{}' format requires: {}", format, required.join(", "));
+    }
+    
+    create_config_file(config_path, None, None)
+}
+
+/// Attach the API key to a request according to the configured auth scheme
+fn authorize(request: RequestBuilder, config: &SubmissionConfig) -> Result<RequestBuilder> {
+    let request = match config.auth_scheme {
+        AuthScheme::Bearer => request.header("Authorization", format!("Bearer {}", config.api_key)),
+        AuthScheme::Basic => {
+            let username = config.username.as_deref()
+                .ok_or_else(|| anyhow::anyhow!("auth_scheme 'basic' requires a 'username' in the config"))?;
+            request.basic_auth(username, Some(&config.api_key))
+        },
+        AuthScheme::Header => {
+            let header = config.auth_header.as_deref().unwrap_or("X-API-Key");
+            request.header(header, config.api_key.as_str())
+        },
+        AuthScheme::None => request,
+    };
+    
+    Ok(request)
+}
+
+/// Join an endpoint path onto the server URL, making sure the result is a valid URL.
+///
+/// Exactly one `/` separates them, so `https://host/api/` with `/check` and `https://host/api`
+/// with `check` both give `https://host/api/check`.
+fn endpoint_url(server_url: &str, path: &str) -> Result<String> {
+    let url = format!("{}/{}", server_url.trim_end_matches('/'), path.trim_start_matches('/'));
+    reqwest::Url::parse(&url)
+        .with_context(|| format!("Invalid endpoint URL '{}' (from server '{}' and path '{}')", url, server_url, path))?;
+    Ok(url)
+}
+
+/// URL of a server endpoint: `server_url`, then `api_prefix` when one is set, then `path`.
+///
+/// Every request to the competition server builds its URL here so a prefix applies to all of them.
+fn api_url(server_url: &str, config: &SubmissionConfig, path: &str) -> Result<String> {
+    match config.api_prefix.as_deref() {
+        Some(prefix) => endpoint_url(&endpoint_url(server_url, prefix)?, path),
+        None => endpoint_url(server_url, path),
+    }
+}
+
+/// Quick reachability check against the server's health endpoint.
+///
+/// A 404 means the server has no health endpoint and is assumed to be up.
+fn check_server_health(client: &Client, server_url: &str, config: &SubmissionConfig) -> Result<()> {
+    let health_url = api_url(server_url, config, &config.health_path)?;
+    
+    let response = client.get(&health_url)
+        .timeout(Duration::from_secs(5))
+        .send()
+        .with_context(|| format!("Server at {} is unreachable (health check failed). Use --no-preflight to skip this check", server_url))?;
+    
+    let status = response.status();
+    if status.is_success() || status == reqwest::StatusCode::NOT_FOUND {
+        return Ok(());
+    }
+    
+    Err(anyhow::anyhow!(
+        "Server health check at {} returned {}. Use --no-preflight to skip this check",
+        health_url,
+        status
+    ))
+}
+
+/// Ask the server whether a submission is allowed, without reporting the answer.
+///
+/// Also returns the server's clock from its `Date` header, when it sent one.
+fn fetch_check(
+    client: &Client,
+    server_url: &str,
+    config: &SubmissionConfig,
+    competition_id: Option<&str>,
+) -> Result<(CheckResponse, Option<u64>)> {
+    let mut check_url = api_url(server_url, config, &config.check_path)?;
+
+    // Add competition_id query parameter if available
+    if let Some(comp_id) = competition_id {
+        check_url = format!("{}?competition={}", check_url, comp_id);
+    }
+
+    status!("🔍 Checking with server: {}", check_url);
+
+    let response = send_with_retry(&config.retry_on, true, "check", || {
+        let request = client.get(&check_url)
+            .timeout(Duration::from_secs(config.check_timeout_secs));
+        Ok(authorize(request, config)?.send()?)
+    })?;
+    let status = response.status();
+    let server_now = check_clock_skew(&response);
+    let body = response.text()?;
+    log_record("check", &check_url, status.as_u16(), &body);
+
+    if !status.is_success() {
+        return Err(anyhow::anyhow!(
+            "Failed to check with server. Status: {}, Body: {}",
+            status,
+            body
+        ));
+    }
+
+    let check_response: CheckResponse = serde_json::from_str(&body)
+        .context("Failed to parse the server's check response")?;
+
+    Ok((check_response, server_now))
+}
+
+/// Print a check response as a table for `optimus status`
+fn print_status(check: &CheckResponse, competition: Option<&str>, now: u64) {
+    let competition = match (&check.competition_name, competition) {
+        (Some(name), Some(id)) => format!("{} ({})", name, id),
+        (Some(name), None) => name.clone(),
+        (None, Some(id)) => id.to_string(),
+        (None, None) => "-".to_string(),
+    };
+    let last_submission = match check.last_submission_by_user {
+        Some(at) => format!("{} ago", format_duration(Duration::from_secs(now.saturating_sub(at)))),
+        None => "never".to_string(),
+    };
+    
+    println!("Competition:      {}", competition);
+    println!("Approved:         {}", if check.submission_approved { "yes" } else { "no" });
+    println!("Attempts left:    {}", check.remaining_attempts);
+    println!("Required format:  {}", check.required_format);
+    println!("Last submission:  {}", last_submission);
+    if let Some(remaining) = check.cooldown_remaining(now) {
+        println!("Next allowed in:  {}", format_duration(remaining));
+    }
+}
+
+/// Check with the server for submission approval and format requirements.
+///
+/// Also returns how long the server's cooldown still blocks the next submission, if at all.
+fn check_with_server(client: &Client, server_url: &str, config: &SubmissionConfig, competition_id: Option<&str>) -> Result<(CheckResponse, Option<Duration>)> {
+    let (check_response, server_now) = fetch_check(client, server_url, config, competition_id)?;
+    // Measure against the server's clock when it sent one, so local skew doesn't distort this
+    let now = server_now.unwrap_or_else(unix_now);
+
+    // Print information about the server response
+    status!("✅ Server requires format: {}", check_response.required_format);
+
+    if check_response.submission_approved {
+        status!("✅ Submission approved. Remaining attempts: {}", check_response.remaining_attempts);
+    } else {
+        message!("❌ Submission not approved. No remaining attempts.");
+    }
+
+    if let Some(competition_name) = &check_response.competition_name {
+        status!("🏆 Competition: {}", competition_name);
+    }
+
+    if let Some(last_submission) = check_response.last_submission_by_user {
+        let duration = Duration::from_secs(now.saturating_sub(last_submission));
+
+        let hours = duration.as_secs() / 3600;
+        let minutes = (duration.as_secs() % 3600) / 60;
+        status!("📊 Last submission was {} hours and {} minutes ago", hours, minutes);
+    } else {
+        status!("📊 No previous submissions found");
+    }
+
+    let cooldown = check_response.cooldown_remaining(now);
+    if let Some(remaining) = cooldown {
+        status!("⏳ Next submission allowed in {}", format_duration(remaining));
+    }
+
+    Ok((check_response, cooldown))
+}
+
+/// Files that make up a Python project, used by the `py` format
+const PYTHON_FORMAT_PATTERNS: &[&str] = &[
+    ".py", "requirements.txt", "pyproject.toml", "setup.py", "setup.cfg", "Pipfile", "Pipfile.lock", "poetry.lock",
+];
+
+/// A file or directory selected for the archive, in the order it will be written
+#[derive(Debug, Clone)]
+struct ArchiveEntry {
+    path: PathBuf,
+    name: String,
+    is_dir: bool,
+}
+
+/// Settings that decide which files are collected for the archive
+#[derive(Debug, Clone, Default)]
+struct CollectOptions {
+    /// Directory whose contents are archived
+    root: PathBuf,
+    format: String,
+    exclude: Vec<String>,
+    /// Globs of the only files to collect; when set, they replace the format's file filter
+    include: Vec<String>,
+    /// The config files in use (canonical paths), which are left out of the archive
+    skip_paths: Vec<PathBuf>,
+    /// Deepest level to include, with the root's direct children at depth 1
+    max_depth: Option<usize>,
+    /// Drop directories that contain no files once filtering is done
+    exclude_empty_dirs: bool,
+    /// Only include files modified at or after this time
+    modified_since: Option<SystemTime>,
+    /// Leave out paths ignored by `.gitignore` files
+    respect_gitignore: bool,
+    /// Archive symlink targets (inside the root only) instead of skipping links
+    follow_symlinks: bool,
+}
+
+/// Fail if `root` is in a git work tree with uncommitted or untracked changes, listing them.
+///
+/// Outside a git repository the check is skipped unless `require_repo` is set.
+fn check_clean_git(root: &Path, require_repo: bool) -> Result<()> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(["status", "--porcelain", "--untracked-files=all"])
+        .output();
+    
+    let output = match output {
+        Ok(output) if output.status.success() => output,
+        // git missing or not a repository
+        _ if require_repo => {
+            return Err(anyhow::anyhow!("{} is not a git repository, but a clean git tree is required", root.display()));
+        }
+        _ => {
+            status!("ℹ️ Not a git repository; skipping the clean tree check");
+            return Ok(());
+        }
+    };
+    
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let dirty: Vec<&str> = stdout.lines().filter(|line| !line.trim().is_empty()).collect();
+    if dirty.is_empty() {
+        status!("✅ Git tree is clean");
+        return Ok(());
+    }
+    
+    message!("❌ Uncommitted changes in the git tree:");
+    for line in &dirty {
+        message!("   {}", line);
+    }
+    Err(anyhow::anyhow!(
+        "Refusing to submit with {} uncommitted or untracked path(s). Commit them or pass --allow-dirty",
+        dirty.len()
+    ))
+}
+
+/// Whether `path` is the file at canonical path `skip`
+fn is_same_file(path: &Path, skip: &Path) -> bool {
+    // Only canonicalize candidates with the right file name to keep the walk cheap
+    path.file_name() == skip.file_name()
+        && path.canonicalize().map(|p| p == skip).unwrap_or(false)
+}
+
+/// Exclude or include patterns compiled once per walk.
+///
+/// Each pattern is a glob (`*.log`, `**/cache`, `docs/*.pdf`) matched against paths relative
+/// to the archive root, with `*` stopping at `/`. A pattern without `/` matches at any depth,
+/// so a plain name like `target` excludes every `target/` directory but not `targeting/` or
+/// `target_notes.txt`.
+struct PathPatterns {
+    patterns: Vec<String>,
+    globs: GlobSet,
+}
+
+impl PathPatterns {
+    fn new(patterns: Vec<String>) -> Result<Self> {
+        let patterns: Vec<String> = patterns.into_iter()
+            .filter(|pattern| !pattern.trim_matches('/').is_empty())
+            .collect();
+        
+        let mut builder = GlobSetBuilder::new();
+        for pattern in &patterns {
+            let trimmed = pattern.trim_matches('/');
+            let glob = if trimmed.contains('/') {
+                trimmed.to_string()
+            } else {
+                format!("**/{}", trimmed)
+            };
+            builder.add(
+                GlobBuilder::new(&glob)
+                    .literal_separator(true)
+                    .build()
+                    .with_context(|| format!("Invalid path pattern '{}'", pattern))?,
+            );
+        }
+        
+        Ok(PathPatterns { patterns, globs: builder.build()? })
+    }
+    
+    /// The first pattern matching `relative`, if any
+    fn matching(&self, relative: &Path) -> Option<&str> {
+        self.globs.matches(relative).first().map(|&index| self.patterns[index].as_str())
+    }
+}
+
+/// Files that usually hold credentials, flagged before every upload
+const DEFAULT_SECRET_PATTERNS: &[&str] = &[
+    ".env",
+    "*.pem",
+    "*.key",
+    "id_rsa",
+    "**/.aws/credentials",
+    "credentials.json",
+];
+
+/// Report archived files that look like secrets, failing instead of warning when `strict`.
+///
+/// This is independent of `exclude`: it checks what actually ended up in the archive.
+fn scan_for_secrets(files: &[ManifestEntry], extra: &[String], strict: bool) -> Result<()> {
+    let patterns = PathPatterns::new(
+        DEFAULT_SECRET_PATTERNS.iter()
+            .map(|pattern| pattern.to_string())
+            .chain(extra.iter().cloned())
+            .collect(),
+    )?;
+    let matches: Vec<(&str, &str)> = files.iter()
+        .filter_map(|file| patterns.matching(Path::new(&file.path)).map(|pattern| (file.path.as_str(), pattern)))
+        .collect();
+    
+    for (path, pattern) in &matches {
+        if strict {
+            message!("❌ Possible secret in archive: {} (matches '{}')", path, pattern);
+        } else {
+            warn(format!("Possible secret in archive: {} (matches '{}')", path, pattern));
+        }
+    }
+    
+    if strict && !matches.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Refusing to upload {} file(s) that look like secrets; exclude them or drop --strict-secrets",
+            matches.len()
+        ));
+    }
+    
+    Ok(())
+}
+
+/// Why the walk left a path out of the archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SkipReason {
+    /// Matched this exclude pattern
+    Excluded(String),
+    /// An archive file, such as one left by an earlier run
+    Archive,
+    /// A config file in use
+    ConfigFile,
+    /// Deeper than `--max-depth`
+    TooDeep,
+    /// Not a file the submission format accepts
+    Format,
+    /// Not matched by any `include` pattern
+    NotIncluded,
+    /// Not modified within `--modified-within`
+    NotModified,
+    /// Ignored by a `.gitignore` file
+    GitIgnored,
+    /// A symlink, while `follow_symlinks` is off
+    Symlink,
+    /// A symlink whose target is outside the archived directory
+    SymlinkEscapesRoot,
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SkipReason::Excluded(pattern) => write!(f, "matches exclude '{}'", pattern),
+            SkipReason::Archive => write!(f, "archive"),
+            SkipReason::ConfigFile => write!(f, "config file in use"),
+            SkipReason::TooDeep => write!(f, "deeper than --max-depth"),
+            SkipReason::Format => write!(f, "not part of the submission format"),
+            SkipReason::NotIncluded => write!(f, "not matched by include"),
+            SkipReason::NotModified => write!(f, "not modified recently"),
+            SkipReason::GitIgnored => write!(f, "ignored by .gitignore"),
+            SkipReason::Symlink => write!(f, "symlink (follow_symlinks is off)"),
+            SkipReason::SymlinkEscapesRoot => write!(f, "symlink pointing outside the project"),
+        }
+    }
+}
+
+/// Walk `root` and collect the entries to archive for the given format and exclusions
+fn collect_entries(options: &CollectOptions) -> Result<Vec<ArchiveEntry>> {
+    walk_entries(options, |_, _| {})
+}
+
+/// Fail when the walk kept no files, so an empty archive never spends an attempt
+fn ensure_files_collected(entries: &[ArchiveEntry], format: &str) -> Result<()> {
+    if entries.iter().any(|entry| !entry.is_dir) {
+        return Ok(());
+    }
+    
+    Err(anyhow::anyhow!(
+        "No files matched for format {} after exclusions; check the directory and your exclude/include patterns (`optimus send --dry-run` shows what was left out)",
+        format
+    ))
+}
+
+/// Paths under `root` that `.gitignore` rules (and `.git/info/exclude`) don't ignore.
+///
+/// Rules apply even outside a git repository, including `.gitignore` files in parent
+/// directories; global git excludes are not consulted.
+fn gitignore_kept_paths(root: &Path, max_depth: Option<usize>, follow_links: bool) -> Result<HashSet<PathBuf>> {
+    let mut kept = HashSet::new();
+    let walker = ignore::WalkBuilder::new(root)
+        .standard_filters(false)
+        .parents(true)
+        .git_ignore(true)
+        .git_exclude(true)
+        .require_git(false)
+        .max_depth(max_depth.map(|depth| depth + 1))
+        .follow_links(follow_links)
+        .build();
+    
+    for entry in walker {
+        let entry = match entry {
+            Ok(entry) => entry,
+            // Broken or looping links are reported by the main walk
+            Err(_) if follow_links => continue,
+            Err(err) => return Err(err).with_context(|| format!("Failed to read .gitignore rules under {}", root.display())),
+        };
+        kept.insert(entry.into_path());
+    }
+    Ok(kept)
+}
+
+/// Walk `root` like `collect_entries`, reporting each path left out and why.
+///
+/// An excluded directory is reported once and not descended into.
+fn walk_entries(options: &CollectOptions, mut on_skip: impl FnMut(&Path, SkipReason)) -> Result<Vec<ArchiveEntry>> {
+    let root = options.root.as_path();
+    let format = options.format.as_str();
+    
+    // Common excluded directories and files
+    let mut excluded = vec![
+        ".git".to_string(), 
+        ".DS_Store".to_string(), 
+        "target".to_string(),
+        "node_modules".to_string(),
+    ];
+    
+    // Add custom exclusions
+    excluded.extend(options.exclude.iter().cloned());
+    let excluded = PathPatterns::new(excluded)?;
+    let included = PathPatterns::new(options.include.clone())?;
+    
+    let mut entries = Vec::new();
+    let mut beyond_depth = 0;
+    let mut skipped_links = 0;
+    
+    // Followed links must resolve inside the root; walkdir itself stops at link cycles
+    let canonical_root = if options.follow_symlinks {
+        Some(std::fs::canonicalize(root).with_context(|| format!("Failed to resolve {}", root.display()))?)
+    } else {
+        None
+    };
+    
+    // Walk one level past the limit so skipped entries can be counted without descending further.
+    // Sorting by name keeps the entry order (directories included) stable across runs.
+    let mut walker = WalkDir::new(root).sort_by_file_name().follow_links(options.follow_symlinks);
+    if let Some(max_depth) = options.max_depth {
+        walker = walker.max_depth(max_depth + 1);
+    }
+    
+    let kept_by_git = if options.respect_gitignore {
+        Some(gitignore_kept_paths(root, options.max_depth, options.follow_symlinks)?)
+    } else {
+        None
+    };
+    
+    let mut walker = walker.into_iter();
+    while let Some(entry) = walker.next() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                warn(format!("Skipping unreadable path: {}", err));
+                continue;
+            }
+        };
+        let path = entry.path();
+        let path_str = path.to_string_lossy();
+        let relative = path.strip_prefix(root)?;
+        
+        // Skip excluded directories and files
+        if let Some(pattern) = excluded.matching(relative) {
+            if entry.file_type().is_dir() {
+                walker.skip_current_dir();
+            }
+            on_skip(relative, SkipReason::Excluded(pattern.to_string()));
+            continue;
+        }
+        
+        if kept_by_git.as_ref().is_some_and(|kept| !kept.contains(path)) {
+            if entry.file_type().is_dir() {
+                walker.skip_current_dir();
+            }
+            on_skip(relative, SkipReason::GitIgnored);
+            continue;
+        }
+        
+        if entry.depth() > 0 && entry.path_is_symlink() {
+            let reason = match &canonical_root {
+                None => Some(SkipReason::Symlink),
+                Some(canonical_root) => match std::fs::canonicalize(path) {
+                    Ok(target) if target.starts_with(canonical_root) => None,
+                    _ => Some(SkipReason::SymlinkEscapesRoot),
+                },
+            };
+            if let Some(reason) = reason {
+                if entry.file_type().is_dir() {
+                    walker.skip_current_dir();
+                }
+                skipped_links += 1;
+                on_skip(relative, reason);
+                continue;
+            }
+        }
+        
+        // Skip archives, including ones left behind by earlier runs
+        if is_archive_name(path) {
+            on_skip(relative, SkipReason::Archive);
+            continue;
+        }
+        
+        // Skip the config file in use, which typically holds the API key
+        if options.skip_paths.iter().any(|skip| is_same_file(path, skip)) {
+            on_skip(relative, SkipReason::ConfigFile);
+            continue;
+        }
+        
+        // Skip if the path is the same as the root directory
+        if path == root {
+            continue;
+        }
+        
+        if options.max_depth.is_some_and(|max_depth| entry.depth() > max_depth) {
+            beyond_depth += 1;
+            on_skip(relative, SkipReason::TooDeep);
+            continue;
+        }
+        
+        let name = relative;
+        
+        // An include list decides which files go in; otherwise the Python format picks its own
+        if !options.include.is_empty() {
+            if path.is_file() && included.matching(relative).is_none() {
+                on_skip(relative, SkipReason::NotIncluded);
+                continue;
+            }
+        } else if format == "py" && path.is_file() {
+            let should_include = PYTHON_FORMAT_PATTERNS.iter()
+                .any(|pattern| path_str.ends_with(pattern));
+            
+            if !should_include {
+                on_skip(relative, SkipReason::Format);
+                continue;
+            }
+        }
+        
+        if path.is_file() && !modified_since(path, options.modified_since) {
+            on_skip(relative, SkipReason::NotModified);
+            continue;
+        }
+        
+        if path.is_file() {
+            entries.push(ArchiveEntry {
+                path: path.to_path_buf(),
+                name: name.to_string_lossy().to_string(),
+                is_dir: false,
+            });
+        } else if path.is_dir() && !name.as_os_str().is_empty() {
+            entries.push(ArchiveEntry {
+                path: path.to_path_buf(),
+                name: name.to_string_lossy().to_string(),
+                is_dir: true,
+            });
+        }
+    }
+    
+    if beyond_depth > 0 {
+        status!(
+            "ℹ️ Skipped {} entries deeper than --max-depth {}",
+            beyond_depth,
+            options.max_depth.unwrap_or_default()
+        );
+    }
+    
+    if skipped_links > 0 {
+        warn(if options.follow_symlinks {
+            format!("Skipped {} symlinks pointing outside the project", skipped_links)
+        } else {
+            format!("Skipped {} symlinks; set follow_symlinks: true to archive what they point to", skipped_links)
+        });
+    }
+    
+    // A modification window or include list keeps only the directories leading to chosen files
+    if options.exclude_empty_dirs || options.modified_since.is_some() || !options.include.is_empty() {
+        entries = drop_empty_dirs(entries);
+    }
+    
+    detail!(
+        "Collected {} files and {} directories",
+        entries.iter().filter(|entry| !entry.is_dir).count(),
+        entries.iter().filter(|entry| entry.is_dir).count()
+    );
+    
+    Ok(entries)
+}
+
+/// Whether the file at `path` was modified at or after `since` (always true without a window).
+///
+/// Files whose modification time can't be read are included with a warning.
+fn modified_since(path: &Path, since: Option<SystemTime>) -> bool {
+    let Some(since) = since else {
+        return true;
+    };
+    
+    match path.metadata().and_then(|meta| meta.modified()) {
+        Ok(modified) => modified >= since,
+        Err(err) => {
+            warn(format!("Including {} with unknown modification time: {}", path.display(), err));
+            true
+        }
+    }
+}
+
+/// Remove directory entries with no file anywhere beneath them
+fn drop_empty_dirs(entries: Vec<ArchiveEntry>) -> Vec<ArchiveEntry> {
+    // Every ancestor of a kept file is non-empty
+    let non_empty: HashSet<PathBuf> = entries.iter()
+        .filter(|entry| !entry.is_dir)
+        .flat_map(|entry| Path::new(&entry.name).ancestors().skip(1).map(Path::to_path_buf))
+        .collect();
+    
+    entries.into_iter()
+        .filter(|entry| !entry.is_dir || non_empty.contains(Path::new(&entry.name)))
+        .collect()
+}
+
+/// Remove a leading directory from entry names, failing if a file would collide with another entry
+fn strip_entry_prefix(entries: Vec<ArchiveEntry>, prefix: &str) -> Result<Vec<ArchiveEntry>> {
+    let prefix = Path::new(prefix.trim_matches('/'));
+    let mut seen: HashMap<String, bool> = HashMap::new();
+    let mut rebased = Vec::new();
+    
+    for mut entry in entries {
+        if let Ok(stripped) = Path::new(&entry.name).strip_prefix(prefix) {
+            // The prefix directory itself becomes the archive root
+            if stripped.as_os_str().is_empty() {
+                continue;
+            }
+            entry.name = stripped.to_string_lossy().to_string();
+        }
+        
+        match seen.get(&entry.name) {
+            // Directories present both inside and outside the prefix merge harmlessly
+            Some(true) if entry.is_dir => continue,
+            Some(_) => {
+                return Err(anyhow::anyhow!(
+                    "'{}' collides with another archive entry after stripping prefix '{}'",
+                    entry.name,
+                    prefix.display()
+                ));
+            },
+            None => {
+                seen.insert(entry.name.clone(), entry.is_dir);
+            },
+        }
+        
+        rebased.push(entry);
+    }
+    
+    Ok(rebased)
+}
+
+/// Prepend a directory to every entry name, adding directory entries for the prefix itself
+fn add_entry_prefix(entries: Vec<ArchiveEntry>, prefix: &str) -> Result<Vec<ArchiveEntry>> {
+    let prefix = prefix.trim_matches('/');
+    let prefix_path = Path::new(prefix);
+    
+    if prefix.is_empty() || prefix_path.components().any(|c| !matches!(c, Component::Normal(_))) {
+        return Err(anyhow::anyhow!(
+            "Invalid --add-prefix '{}'. Expected a relative directory path without '..'",
+            prefix
+        ));
+    }
+    
+    // Directory entries for each level of the prefix, so extractors create them.
+    // These are synthetic and have no source path on disk.
+    let mut nested = Vec::new();
+    let mut dir = String::new();
+    for component in prefix_path.components() {
+        if !dir.is_empty() {
+            dir.push('/');
+        }
+        dir.push_str(&component.as_os_str().to_string_lossy());
+        nested.push(ArchiveEntry {
+            path: PathBuf::new(),
+            name: dir.clone(),
+            is_dir: true,
+        });
+    }
+    
+    for mut entry in entries {
+        entry.name = format!("{}/{}", dir, entry.name);
+        nested.push(entry);
+    }
+    
+    Ok(nested)
+}
+
+/// How collected entry paths are reshaped inside the archive
+#[derive(Debug, Clone, Default)]
+struct EntryLayout {
+    strip_prefix: Option<String>,
+    add_prefix: Option<String>,
+}
+
+/// Apply `--strip-prefix` then `--add-prefix` to the collected entries
+fn apply_layout(mut entries: Vec<ArchiveEntry>, layout: &EntryLayout) -> Result<Vec<ArchiveEntry>> {
+    if let Some(prefix) = &layout.strip_prefix {
+        entries = strip_entry_prefix(entries, prefix)?;
+    }
+    
+    if let Some(prefix) = &layout.add_prefix {
+        entries = add_entry_prefix(entries, prefix)?;
+    }
+    
+    Ok(entries)
+}
+
+/// File contents loaded for the archive, hashed on the thread that read them
+struct EntryData {
+    contents: Vec<u8>,
+    sha256: Option<String>,
+}
+
+/// Read and hash the contents of an entry (empty and unhashed for directories)
+fn read_entry(entry: &ArchiveEntry) -> Result<EntryData> {
+    if entry.is_dir {
+        return Ok(EntryData { contents: Vec::new(), sha256: None });
+    }
+    
+    let contents = std::fs::read(&entry.path)
+        .with_context(|| format!("Failed to read {}", entry.path.display()))?;
+    let sha256 = format!("{:x}", Sha256::digest(&contents));
+    
+    Ok(EntryData { contents, sha256: Some(sha256) })
+}
+
+/// Permission bits stored for every file and directory entry, whatever the mode on disk,
+/// so the same tree builds the same archive on any machine
+const ENTRY_MODE: u32 = 0o755;
+
+/// Add a single entry to the zip archive, returning its manifest record for files
+fn write_entry<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    entry: &ArchiveEntry,
+    data: EntryData,
+    options: FileOptions,
+    deterministic: bool,
+) -> Result<Option<ManifestEntry>> {
+    let options = if deterministic {
+        options
+    } else {
+        options.last_modified_time(zip_modified_time(&entry.path))
+    };
+    
+    if entry.is_dir {
+        zip.add_directory(entry.name.clone(), options.unix_permissions(ENTRY_MODE))?;
+        return Ok(None);
+    }
+    
+    zip.start_file(entry.name.clone(), options)?;
+    zip.write_all(&data.contents)?;
+    
+    Ok(data.sha256.map(|sha256| ManifestEntry {
+        path: entry.name.clone(),
+        size: data.contents.len() as u64,
+        sha256,
+    }))
+}
+
+/// Progress bar counting files written to an archive, or a hidden one when `show` is false
+fn archive_progress(show: bool, files: u64) -> Result<ProgressBar> {
+    if !show {
+        return Ok(ProgressBar::hidden());
+    }
+    
+    let bar = ProgressBar::new(files);
+    bar.set_style(ProgressStyle::with_template("{bar:40} {pos}/{len} files ({eta})")?);
+    Ok(bar)
+}
+
+/// Write entries to the zip, reading file contents on `read_threads` threads.
+///
+/// Readers claim entries in order, hash each file's bytes and hand them to the writer
+/// over a bounded channel; the writer reorders them so the archive layout matches the
+/// sequential path. Every file is read exactly once for both hashing and compression.
+/// Readers never run more than `read_threads * 2` entries ahead of the writer, so a
+/// slow entry can't make the reorder buffer hold the rest of the tree in memory.
+/// Unless `deterministic`, entries keep their modification times from disk.
+fn write_entries<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    entries: &[ArchiveEntry],
+    options: FileOptions,
+    deterministic: bool,
+    read_threads: usize,
+    progress: &ProgressBar,
+) -> Result<Vec<ManifestEntry>> {
+    let mut files = Vec::new();
+    
+    if read_threads <= 1 {
+        for entry in entries {
+            let data = read_entry(entry)?;
+            files.extend(write_entry(zip, entry, data, options, deterministic)?);
+            if !entry.is_dir {
+                progress.inc(1);
+            }
+        }
+        return Ok(files);
+    }
+    
+    let next_index = AtomicUsize::new(0);
+    let window = read_threads * 2;
+    let written = WriterProgress::default();
+    
+    thread::scope(|scope| -> Result<()> {
+        // The channel lives inside the scope so an early writer error drops the
+        // receiver and unblocks any reader waiting to send
+        let (sender, receiver) = mpsc::sync_channel::<(usize, Result<EntryData>)>(window);
+        // Releases readers waiting on the window however the writer loop exits
+        let _release = WriterDone(&written);
+        
+        for _ in 0..read_threads {
+            let sender = sender.clone();
+            let next_index = &next_index;
+            let written = &written;
+            scope.spawn(move || loop {
+                let index = next_index.fetch_add(1, Ordering::SeqCst);
+                let Some(entry) = entries.get(index) else { break };
+                written.wait_until_within(index, window);
+                if sender.send((index, read_entry(entry))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+        
+        let mut pending = BTreeMap::new();
+        for (index, entry) in entries.iter().enumerate() {
+            let data = loop {
+                if let Some(data) = pending.remove(&index) {
+                    break data;
+                }
+                let (received, data) = receiver.recv()
+                    .context("File reader threads stopped unexpectedly")?;
+                pending.insert(received, data);
+            };
+            files.extend(write_entry(zip, entry, data?, options, deterministic)?);
+            written.advance(index + 1);
+            if !entry.is_dir {
+                progress.inc(1);
+            }
+        }
+        
+        Ok(())
+    })?;
+    
+    Ok(files)
+}
+
+/// How many entries the archive writer has finished, shared with the reader threads
+#[derive(Default)]
+struct WriterProgress {
+    written: Mutex<usize>,
+    changed: Condvar,
+}
+
+impl WriterProgress {
+    /// Block until entry `index` is fewer than `window` entries ahead of the writer
+    fn wait_until_within(&self, index: usize, window: usize) {
+        let mut written = self.written.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        while index >= written.saturating_add(window) {
+            written = self.changed.wait(written).unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+    
+    fn advance(&self, count: usize) {
+        *self.written.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = count;
+        self.changed.notify_all();
+    }
+}
+
+/// Marks the writer finished when dropped, so no reader is left waiting after an error
+struct WriterDone<'a>(&'a WriterProgress);
+
+impl Drop for WriterDone<'_> {
+    fn drop(&mut self) {
+        self.0.advance(usize::MAX);
+    }
+}
+
+/// A file in the submission manifest
+#[derive(Deserialize, Serialize, Debug, Clone)]
+struct ManifestEntry {
+    path: String,
+    size: u64,
+    sha256: String,
+}
+
+/// Machine-readable description of what a submission contains
+#[derive(Deserialize, Serialize, Debug)]
+struct Manifest {
+    format: String,
+    files: Vec<ManifestEntry>,
+}
+
+/// Print the files a submission would include and those left out, with sizes and a total
+fn print_dry_run(options: &CollectOptions, layout: &EntryLayout) -> Result<()> {
+    let mut skipped = Vec::new();
+    let entries = walk_entries(options, |path, reason| skipped.push((path.to_path_buf(), reason)))?;
+    let entries = apply_layout(entries, layout)?;
+    
+    println!("🧪 Dry run: nothing will be archived or uploaded");
+    println!("Included:");
+    let mut total = 0;
+    let mut file_count = 0;
+    for entry in entries.iter().filter(|entry| !entry.is_dir) {
+        let size = std::fs::metadata(&entry.path)?.len();
+        total += size;
+        file_count += 1;
+        println!("   {:>10}  {}", format_bytes(size), entry.name);
+    }
+    
+    if !skipped.is_empty() {
+        println!("Excluded:");
+        for (path, reason) in &skipped {
+            println!("   {} ({})", path.display(), reason);
+        }
+    }
+    
+    println!("Total: {} files, {} before compression", file_count, format_bytes(total));
+    Ok(())
+}
+
+/// Build a manifest for the collected entries, hashing each file
+fn build_manifest(format: &str, entries: &[ArchiveEntry]) -> Result<Manifest> {
+    let mut files = Vec::new();
+    
+    for entry in entries.iter().filter(|e| !e.is_dir) {
+        files.push(ManifestEntry {
+            path: entry.name.clone(),
+            size: std::fs::metadata(&entry.path)?.len(),
+            sha256: sha256_file(&entry.path)?,
+        });
+    }
+    
+    Ok(Manifest {
+        format: format.to_string(),
+        files,
+    })
+}
+
+/// File set a competition expects, as given to `--manifest-against`.
+///
+/// Paths ending in `/` in `allowed` permit anything beneath that directory.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct ExpectedManifest {
+    /// Files that must be present
+    required: Vec<String>,
+    /// Further files that may be present
+    allowed: Vec<String>,
+}
+
+/// Differences between the collected files and an expected manifest
+#[derive(Debug, Default)]
+struct ManifestMismatch {
+    missing: Vec<String>,
+    extra: Vec<String>,
+}
+
+impl ManifestMismatch {
+    fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty()
+    }
+}
+
+/// Read an expected manifest file (JSON is accepted as YAML)
+fn read_expected_manifest(path: &str) -> Result<ExpectedManifest> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open expected manifest '{}'", path))?;
+    serde_yaml::from_reader(file)
+        .with_context(|| format!("Failed to parse expected manifest '{}'", path))
+}
+
+/// Find required files that weren't collected and collected files the manifest doesn't allow
+fn check_manifest_compliance(expected: &ExpectedManifest, entries: &[ArchiveEntry]) -> ManifestMismatch {
+    let files: HashSet<&str> = entries.iter()
+        .filter(|entry| !entry.is_dir)
+        .map(|entry| entry.name.as_str())
+        .collect();
+    
+    let missing = expected.required.iter()
+        .filter(|path| !files.contains(path.as_str()))
+        .cloned()
+        .collect();
+    
+    let is_allowed = |name: &str| {
+        expected.required.iter().chain(expected.allowed.iter()).any(|path| {
+            name == path || (path.ends_with('/') && name.starts_with(path.as_str()))
+        })
+    };
+    let mut extra: Vec<String> = files.into_iter()
+        .filter(|name| !is_allowed(name))
+        .map(str::to_string)
+        .collect();
+    extra.sort();
+    
+    ManifestMismatch { missing, extra }
+}
+
+/// An archive written to disk along with the files it contains.
+///
+/// `files` carries per-file SHA-256 digests of the uncompressed contents, computed from
+/// the same bytes fed to the compressor. The whole-archive digest (see `sha256_file`) is
+/// computed separately with a single streaming read of the finished archive, since the
+/// zip writer seeks back to patch headers and can't be hashed as it is written.
+struct BuiltArchive {
+    path: PathBuf,
+    files: Vec<ManifestEntry>,
+    dir_count: usize,
+}
+
+/// Print the file and directory counts, the largest file and the size before and after compression
+fn print_archive_summary(files: &[ManifestEntry], dir_count: usize, archive_bytes: u64) {
+    let uncompressed: u64 = files.iter().map(|f| f.size).sum();
+    
+    status!("📊 Archive summary: {} files, {} directories", files.len(), dir_count);
+    if let Some(largest) = files.iter().max_by_key(|f| f.size) {
+        status!("   Largest file: {} ({})", largest.path, format_bytes(largest.size));
+    }
+    status!(
+        "   Total size: {} uncompressed, {} compressed",
+        format_bytes(uncompressed),
+        format_bytes(archive_bytes)
+    );
+}
+
+/// Name of the directory being archived, used to name the archive
+fn root_dir_name(root: &Path) -> Result<String> {
+    let dir_name = root.file_name()
+        .context("Failed to get directory name")?
+        .to_string_lossy()
+        .to_string();
+    
+    Ok(dir_name)
+}
+
+/// Free space below which the archive temp dir gets a warning
+const LOW_TEMP_SPACE_BYTES: u64 = 512 * 1024 * 1024;
+
+/// Temp directory from the flag, then `OPTIMUS_TMPDIR`, then the system default
+fn configured_temp_dir(flag: Option<&str>) -> PathBuf {
+    match flag.map(String::from).or_else(|| env::var("OPTIMUS_TMPDIR").ok()) {
+        Some(dir) => PathBuf::from(dir),
+        None => env::temp_dir(),
+    }
+}
+
+/// Comment stamped on every archive this tool writes, so `optimus clean` only removes its own
+const ARCHIVE_MARKER: &str = "Created by optimus";
+
+/// File name endings of the archive formats optimus writes
+const ARCHIVE_SUFFIXES: &[&str] = &[".zip", ".tar.gz"];
+
+/// Whether `path` is named like an archive optimus could have written
+fn is_archive_name(path: &Path) -> bool {
+    let name = path.file_name().map(|name| name.to_string_lossy().to_lowercase()).unwrap_or_default();
+    ARCHIVE_SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+}
+
+/// Whether `path` is a zip or tarball carrying the optimus marker comment
+fn is_optimus_archive(path: &Path) -> bool {
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    
+    if name.ends_with(".zip") {
+        return File::open(path).ok()
+            .and_then(|file| zip::ZipArchive::new(file).ok())
+            .is_some_and(|archive| archive.comment() == ARCHIVE_MARKER.as_bytes());
+    }
+    if name.ends_with(".tar.gz") {
+        return File::open(path).ok()
+            .map(flate2::read::GzDecoder::new)
+            .is_some_and(|decoder| {
+                decoder.header().and_then(|header| header.comment()) == Some(ARCHIVE_MARKER.as_bytes())
+            });
+    }
+    
+    false
+}
+
+/// Remove (or with `dry_run`, list) archives that interrupted runs left in the temp directory
+fn run_clean(temp_dir: Option<&str>, dry_run: bool) -> Result<()> {
+    let dir = configured_temp_dir(temp_dir);
+    
+    let mut leftovers = Vec::new();
+    for entry in std::fs::read_dir(&dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_file() && is_optimus_archive(&path) {
+            leftovers.push((std::fs::metadata(&path)?.len(), path));
+        }
+    }
+    
+    if leftovers.is_empty() {
+        println!("✨ No leftover archives in {}", dir.display());
+        return Ok(());
+    }
+    
+    let total: u64 = leftovers.iter().map(|(size, _)| size).sum();
+    for (size, path) in &leftovers {
+        if dry_run {
+            println!("  {} ({})", path.display(), format_bytes(*size));
+        } else {
+            std::fs::remove_file(path)
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+            println!("🗑️ Removed {} ({})", path.display(), format_bytes(*size));
+        }
+    }
+    
+    if dry_run {
+        println!("🔍 {} leftover archive(s); cleaning would free {}", leftovers.len(), format_bytes(total));
+    } else {
+        println!("🧹 Removed {} leftover archive(s), freed {}", leftovers.len(), format_bytes(total));
+    }
+    Ok(())
+}
+
+/// Pick and validate the directory intermediate archives are written to.
+///
+/// Uses `--temp-dir`, then `OPTIMUS_TMPDIR`, then the system temp dir (which honors `TMPDIR`).
+fn archive_temp_dir(flag: Option<&str>) -> Result<PathBuf> {
+    let dir = configured_temp_dir(flag);
+    
+    if !dir.is_dir() {
+        return Err(anyhow::anyhow!("Temp directory {} does not exist or is not a directory", dir.display()));
+    }
+    tempfile::tempfile_in(&dir)
+        .with_context(|| format!("Temp directory {} is not writable", dir.display()))?;
+    
+    if let Some(available) = available_space(&dir) {
+        if available < LOW_TEMP_SPACE_BYTES {
+            warn(format!(
+                "Only {} free in {}; large archives may not fit (use --temp-dir or OPTIMUS_TMPDIR)",
+                format_bytes(available),
+                dir.display()
+            ));
+        }
+    }
+    
+    Ok(dir)
+}
+
+/// Best-effort free space on the filesystem holding `dir`, via `df`
+fn available_space(dir: &Path) -> Option<u64> {
+    if !cfg!(unix) {
+        return None;
+    }
+    
+    let output = Command::new("df").arg("-Pk").arg(dir).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    
+    // POSIX format: the second line's fourth column is the available space in KiB
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let available_kib: u64 = stdout.lines().nth(1)?.split_whitespace().nth(3)?.parse().ok()?;
+    Some(available_kib * 1024)
+}
+
+/// Path in `temp_dir` for an archive named after the archived directory, removing any stale copy
+fn temp_archive_path(temp_dir: &Path, root: &Path, suffix: &str, extension: &str) -> Result<PathBuf> {
+    let dir_name = root_dir_name(root)?;
+    
+    let zip_path = temp_dir.join(format!("{}{}.{}", dir_name, suffix, extension));
+    
+    // Delete the zip file if it already exists
+    if zip_path.exists() {
+        std::fs::remove_file(&zip_path)?;
+    }
+    
+    Ok(zip_path)
+}
+
+/// Container format of the uploaded archive
+#[derive(ValueEnum, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+enum ArchiveFormat {
+    /// A zip archive
+    #[default]
+    Zip,
+    /// A gzip-compressed tarball (.tar.gz)
+    Targz,
+}
+
+impl ArchiveFormat {
+    /// Name used for the format in the config and on the command line
+    fn as_str(self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::Targz => "targz",
+        }
+    }
+    
+    /// File name endings an archive of this format may be given
+    fn suffixes(self) -> &'static [&'static str] {
+        match self {
+            ArchiveFormat::Zip => &[".zip"],
+            ArchiveFormat::Targz => &[".tar.gz", ".tgz"],
+        }
+    }
+}
+
+/// How archive entries are compressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CompressionSettings {
+    method: zip::CompressionMethod,
+    level: u8,
+}
+
+/// What `--optimize-for` should favour when picking compression settings
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum OptimizeFor {
+    /// Smallest archive: zstd at level 19
+    Size,
+    /// Fastest build: deflate at level 1
+    Speed,
+    /// The defaults: deflate at level 6
+    Balanced,
+}
+
+/// Parse a compression method name as accepted by `--compression-method`
+fn parse_compression_method(name: &str) -> Result<zip::CompressionMethod> {
+    match name.to_ascii_lowercase().as_str() {
+        "stored" | "store" => Ok(zip::CompressionMethod::Stored),
+        "deflate" | "deflated" => Ok(zip::CompressionMethod::Deflated),
+        #[cfg(feature = "bzip2")]
+        "bzip2" => Ok(zip::CompressionMethod::Bzip2),
+        #[cfg(not(feature = "bzip2"))]
+        "bzip2" => Err(anyhow::anyhow!("This build of optimus doesn't support bzip2 compression (see `optimus version`)")),
+        #[cfg(feature = "zstd")]
+        "zstd" => Ok(zip::CompressionMethod::Zstd),
+        #[cfg(not(feature = "zstd"))]
+        "zstd" => Err(anyhow::anyhow!("This build of optimus doesn't support zstd compression (see `optimus version`)")),
+        _ => Err(anyhow::anyhow!(
+            "Unsupported compression method: {}. Expected stored, deflate, bzip2 or zstd",
+            name
+        )),
+    }
+}
+
+/// The compression method from the flag, then the config, defaulting to deflate
+fn configured_compression_method(flag: Option<&str>, config: &SubmissionConfig) -> Result<zip::CompressionMethod> {
+    match flag.or(config.compression_method.as_deref()) {
+        Some(name) => parse_compression_method(name),
+        None => Ok(zip::CompressionMethod::Deflated),
+    }
+}
+
+/// Compression settings for an `--optimize-for` goal
+fn compression_for_goal(goal: OptimizeFor) -> CompressionSettings {
+    match goal {
+        #[cfg(feature = "zstd")]
+        OptimizeFor::Size => CompressionSettings { method: zip::CompressionMethod::Zstd, level: 19 },
+        #[cfg(not(feature = "zstd"))]
+        OptimizeFor::Size => CompressionSettings { method: zip::CompressionMethod::Deflated, level: 9 },
+        OptimizeFor::Speed => CompressionSettings { method: zip::CompressionMethod::Deflated, level: 1 },
+        OptimizeFor::Balanced => CompressionSettings {
+            method: zip::CompressionMethod::Deflated,
+            level: default_compression_level(),
+        },
+    }
+}
+
+/// Options applied to every entry written to the zip
+fn zip_file_options(compression: CompressionSettings) -> FileOptions {
+    // Stored entries reject any compression level
+    let level = match compression.method {
+        zip::CompressionMethod::Stored => None,
+        _ => Some(compression.level.into()),
+    };
+    
+    // A fixed timestamp (the zip epoch, 1980-01-01) keeps archives of the same tree
+    // byte-identical; entries are already added in sorted walk order
+    FileOptions::default()
+        .compression_method(compression.method)
+        .unix_permissions(ENTRY_MODE)
+        .compression_level(level)
+        .last_modified_time(zip::DateTime::default())
+}
+
+/// Counts, sizes and digests describing an archive produced by `ArchiveBuilder`
+#[derive(Serialize, Debug)]
+struct ArchiveStats {
+    file_count: usize,
+    dir_count: usize,
+    /// Total size of the files before compression
+    uncompressed_bytes: u64,
+    /// Size of the finished archive
+    archive_bytes: u64,
+    /// SHA-256 over each file's path and digest, independent of compression settings
+    content_sha256: String,
+    files: Vec<ManifestEntry>,
+}
+
+/// Chainable builder for a zip archive of a directory.
+///
+/// Collection, layout and compression are configured up front and the archive is
+/// written to any seekable writer, e.g. an in-memory buffer:
+///
+/// ```ignore
+/// let mut buffer = std::io::Cursor::new(Vec::new());
+/// let stats = ArchiveBuilder::new("project")
+///     .format("py")
+///     .exclude(vec!["data".to_string()])
+///     .compression(CompressionSettings { method: zip::CompressionMethod::Deflated, level: 9 })
+///     .deterministic(false)
+///     .build_to(&mut buffer)?;
+/// println!("{} files, {} bytes", stats.file_count, stats.archive_bytes);
+/// ```
+struct ArchiveBuilder {
+    collect: CollectOptions,
+    layout: EntryLayout,
+    compression: CompressionSettings,
+    deterministic: bool,
+    read_threads: usize,
+    show_progress: bool,
+}
+
+impl ArchiveBuilder {
+    /// Start a repository-format archive of `root` with the default compression
+    fn new(root: impl Into<PathBuf>) -> Self {
+        ArchiveBuilder {
+            collect: CollectOptions {
+                root: root.into(),
+                format: "repo".to_string(),
+                ..Default::default()
+            },
+            layout: EntryLayout::default(),
+            compression: CompressionSettings {
+                method: zip::CompressionMethod::Deflated,
+                level: default_compression_level(),
+            },
+            deterministic: true,
+            read_threads: 1,
+            show_progress: false,
+        }
+    }
+    
+    fn format(mut self, format: &str) -> Self {
+        self.collect.format = format.to_string();
+        self
+    }
+    
+    /// Glob patterns of paths to leave out, in addition to the built-in exclusions
+    fn exclude(mut self, exclude: Vec<String>) -> Self {
+        self.collect.exclude = exclude;
+        self
+    }
+    
+    /// Globs of the only files to include, replacing the format's file filter
+    fn include(mut self, include: Vec<String>) -> Self {
+        self.collect.include = include;
+        self
+    }
+    
+    /// Files (canonical paths) to leave out, such as the config in use
+    fn skip_paths(mut self, skip_paths: Vec<PathBuf>) -> Self {
+        self.collect.skip_paths = skip_paths;
+        self
+    }
+    
+    fn max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.collect.max_depth = max_depth;
+        self
+    }
+    
+    fn exclude_empty_dirs(mut self, exclude_empty_dirs: bool) -> Self {
+        self.collect.exclude_empty_dirs = exclude_empty_dirs;
+        self
+    }
+    
+    /// Leave out paths ignored by `.gitignore` files
+    fn respect_gitignore(mut self, respect_gitignore: bool) -> Self {
+        self.collect.respect_gitignore = respect_gitignore;
+        self
+    }
+    
+    /// Archive symlink targets inside the root instead of skipping links
+    fn follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.collect.follow_symlinks = follow_symlinks;
+        self
+    }
+    
+    fn modified_since(mut self, modified_since: Option<SystemTime>) -> Self {
+        self.collect.modified_since = modified_since;
+        self
+    }
+    
+    fn layout(mut self, layout: EntryLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+    
+    fn compression(mut self, compression: CompressionSettings) -> Self {
+        self.compression = compression;
+        self
+    }
+    
+    /// Build the same bytes for the same tree on every run (the default): entries get a
+    /// fixed timestamp instead of their modification times. Order and modes are fixed either way.
+    fn deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+    
+    fn read_threads(mut self, read_threads: usize) -> Self {
+        self.read_threads = read_threads;
+        self
+    }
+    
+    /// Show a bar counting files as they are written
+    fn show_progress(mut self, show_progress: bool) -> Self {
+        self.show_progress = show_progress;
+        self
+    }
+    
+    /// Collect the entries and write the archive to `writer`
+    fn build_to<W: Write + Seek>(self, writer: W) -> Result<ArchiveStats> {
+        let entries = collect_entries(&self.collect)?;
+        ensure_files_collected(&entries, &self.collect.format)?;
+        let entries = apply_layout(entries, &self.layout)?;
+        
+        let file_total = entries.iter().filter(|e| !e.is_dir).count() as u64;
+        let progress = archive_progress(self.show_progress, file_total)?;
+        
+        let mut zip = ZipWriter::new(writer);
+        zip.set_comment(ARCHIVE_MARKER);
+        let options = zip_file_options(self.compression);
+        let files = write_entries(&mut zip, &entries, options, self.deterministic, self.read_threads, &progress)?;
+        progress.finish_and_clear();
+        let mut writer = zip.finish()?;
+        
+        let mut content_hasher = Sha256::new();
+        for file in &files {
+            content_hasher.update(format!("{}\0{}\n", file.path, file.sha256));
+        }
+        
+        Ok(ArchiveStats {
+            file_count: files.len(),
+            dir_count: entries.iter().filter(|e| e.is_dir).count(),
+            uncompressed_bytes: files.iter().map(|f| f.size).sum(),
+            archive_bytes: writer.stream_position()?,
+            content_sha256: format!("{:x}", content_hasher.finalize()),
+            files,
+        })
+    }
+}
+
+/// Create a zip archive based on the specified format and exclusions
+fn create_zip_archive(
+    compression: CompressionSettings,
+    collect: &CollectOptions,
+    read_threads: usize,
+    layout: &EntryLayout,
+    temp_dir: &Path,
+    show_progress: bool,
+) -> Result<BuiltArchive> {
+    let zip_path = temp_archive_path(temp_dir, &collect.root, "", "zip")?;
+    
+    match collect.format.as_str() {
+        "py" => status!("🐍 Using Python format: Only including Python files and project configuration"),
+        _ => status!("📦 Using Repository format: Including all files except excluded ones"),
+    }
+    
+    status!("🔄 Creating zip archive...");
+    
+    let stats = ArchiveBuilder::new(&collect.root)
+        .format(&collect.format)
+        .exclude(collect.exclude.clone())
+        .include(collect.include.clone())
+        .skip_paths(collect.skip_paths.clone())
+        .max_depth(collect.max_depth)
+        .exclude_empty_dirs(collect.exclude_empty_dirs)
+        .respect_gitignore(collect.respect_gitignore)
+        .follow_symlinks(collect.follow_symlinks)
+        .modified_since(collect.modified_since)
+        .layout(layout.clone())
+        .compression(compression)
+        // Identical trees must give identical archives and checksums
+        .deterministic(true)
+        .read_threads(read_threads)
+        .show_progress(show_progress)
+        .build_to(File::create(&zip_path)?)?;
+    
+    Ok(BuiltArchive { path: zip_path, files: stats.files, dir_count: stats.dir_count })
+}
+
+/// Create a gzip-compressed tarball holding the same entries `create_zip_archive` would.
+///
+/// Only the compression level applies; the zip compression method is ignored.
+fn create_targz_archive(
+    compression: CompressionSettings,
+    collect: &CollectOptions,
+    layout: &EntryLayout,
+    temp_dir: &Path,
+    show_progress: bool,
+) -> Result<BuiltArchive> {
+    let tar_path = temp_archive_path(temp_dir, &collect.root, "", "tar.gz")?;
+    
+    match collect.format.as_str() {
+        "py" => status!("🐍 Using Python format: Only including Python files and project configuration"),
+        _ => status!("📦 Using Repository format: Including all files except excluded ones"),
+    }
+    
+    status!("🔄 Creating tar.gz archive...");
+    
+    let entries = collect_entries(collect)?;
+    ensure_files_collected(&entries, &collect.format)?;
+    let entries = apply_layout(entries, layout)?;
+    let progress = archive_progress(show_progress, entries.iter().filter(|e| !e.is_dir).count() as u64)?;
+    
+    let level = flate2::Compression::new(u32::from(compression.level.min(9)));
+    let encoder = flate2::GzBuilder::new()
+        .comment(ARCHIVE_MARKER)
+        .write(File::create(&tar_path)?, level);
+    let mut tar = tar::Builder::new(encoder);
+    let mut files = Vec::new();
+    
+    for entry in &entries {
+        let data = read_entry(entry)?;
+        
+        // Fixed timestamps and owners keep the tarball identical across runs, like the zip
+        let mut header = tar::Header::new_gnu();
+        header.set_mtime(0);
+        header.set_size(data.contents.len() as u64);
+        if entry.is_dir {
+            header.set_entry_type(tar::EntryType::Directory);
+        } else {
+            header.set_entry_type(tar::EntryType::Regular);
+        }
+        header.set_mode(ENTRY_MODE);
+        tar.append_data(&mut header, &entry.name, data.contents.as_slice())
+            .with_context(|| format!("Failed to add {} to the tarball", entry.name))?;
+        
+        if let Some(sha256) = data.sha256 {
+            files.push(ManifestEntry {
+                path: entry.name.clone(),
+                size: data.contents.len() as u64,
+                sha256,
+            });
+            progress.inc(1);
+        }
+    }
+    
+    tar.into_inner()?.finish()?;
+    progress.finish_and_clear();
+    
+    let dir_count = entries.iter().filter(|e| e.is_dir).count();
+    Ok(BuiltArchive { path: tar_path, files, dir_count })
+}
+
+/// Name of the metadata file stored at the root of a delta package
+const DELTA_METADATA_NAME: &str = ".optimus-delta.json";
+
+/// Describes how a delta package applies on top of the server's base submission
+#[derive(Serialize, Debug)]
+struct DeltaMetadata {
+    format: String,
+    changed: Vec<String>,
+    deleted: Vec<String>,
+}
+
+/// Download the manifest of the submission a delta will be applied to
+fn fetch_base_manifest(client: &Client, server_url: &str, manifest_url: &str, config: &SubmissionConfig) -> Result<Manifest> {
+    // The server may hand back a path relative to its own base URL
+    let manifest_url = if manifest_url.starts_with('/') {
+        endpoint_url(server_url, manifest_url)?
+    } else {
+        manifest_url.to_string()
+    };
+    
+    status!("🔍 Fetching base manifest: {}", manifest_url);
+    
+    let response = send_with_retry(&config.retry_on, true, "base manifest", || {
+        let request = client.get(&manifest_url)
+            .timeout(Duration::from_secs(config.check_timeout_secs));
+        Ok(authorize(request, config)?.send()?)
+    })?;
+    
+    if !response.status().is_success() {
+        return Err(anyhow::anyhow!(
+            "Failed to fetch base manifest. Status: {}, Body: {}",
+            response.status(),
+            response.text().unwrap_or_default()
+        ));
+    }
+    
+    Ok(response.json()?)
+}
+
+/// Files that differ between a base manifest and the current one
+#[derive(Debug, PartialEq, Eq)]
+struct ManifestDiff {
+    /// New files and files whose SHA-256 changed, in the current manifest's order
+    changed: Vec<String>,
+    /// Files in the base that are no longer present, in the base's order
+    deleted: Vec<String>,
+}
+
+/// Compare the current manifest against the server's base by path and SHA-256
+fn diff_manifests(base: &Manifest, current: &Manifest) -> ManifestDiff {
+    let base_hashes: HashMap<&str, &str> = base.files.iter()
+        .map(|f| (f.path.as_str(), f.sha256.as_str()))
+        .collect();
+    let current_paths: HashSet<&str> = current.files.iter()
+        .map(|f| f.path.as_str())
+        .collect();
+    
+    ManifestDiff {
+        changed: current.files.iter()
+            .filter(|f| base_hashes.get(f.path.as_str()) != Some(&f.sha256.as_str()))
+            .map(|f| f.path.clone())
+            .collect(),
+        deleted: base.files.iter()
+            .filter(|f| !current_paths.contains(f.path.as_str()))
+            .map(|f| f.path.clone())
+            .collect(),
+    }
+}
+
+/// Create a delta package holding only files that differ from `base`, plus a deletion list
+fn create_delta_archive(
+    compression: CompressionSettings,
+    collect: &CollectOptions,
+    read_threads: usize,
+    layout: &EntryLayout,
+    base: &Manifest,
+    temp_dir: &Path,
+    show_progress: bool,
+) -> Result<BuiltArchive> {
+    let entries = collect_entries(collect)?;
+    ensure_files_collected(&entries, &collect.format)?;
+    let entries = apply_layout(entries, layout)?;
+    let current = build_manifest(&collect.format, &entries)?;
+    
+    let ManifestDiff { changed: changed_paths, deleted } = diff_manifests(base, &current);
+    let changed_paths: HashSet<&str> = changed_paths.iter().map(String::as_str).collect();
+    let changed: Vec<ArchiveEntry> = entries.into_iter()
+        .filter(|e| !e.is_dir && changed_paths.contains(e.name.as_str()))
+        .collect();
+    
+    status!(
+        "🧩 Delta against base: {} changed, {} deleted, {} unchanged",
+        changed.len(),
+        deleted.len(),
+        current.files.len() - changed.len()
+    );
+    
+    let zip_path = temp_archive_path(temp_dir, &collect.root, "-delta", "zip")?;
+    let options = zip_file_options(compression);
+    let mut zip = ZipWriter::new(File::create(&zip_path)?);
+    zip.set_comment(ARCHIVE_MARKER);
+    
+    let progress = archive_progress(show_progress, changed.len() as u64)?;
+    let files = write_entries(&mut zip, &changed, options, true, read_threads, &progress)?;
+    progress.finish_and_clear();
+    
+    let metadata = DeltaMetadata {
+        format: collect.format.clone(),
+        changed: changed.iter().map(|e| e.name.clone()).collect(),
+        deleted,
+    };
+    zip.start_file(DELTA_METADATA_NAME, options)?;
+    zip.write_all(serde_json::to_string_pretty(&metadata)?.as_bytes())?;
+    zip.finish()?;
+    
+    // A delta only carries changed files, never directory entries
+    Ok(BuiltArchive { path: zip_path, files, dir_count: 0 })
+}
+
+/// Format a byte count for display, e.g. `1.5 MB`
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
+    
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Parse a human size such as `500KB`, `50MB` or `1.5GB` (a bare number is bytes).
+///
+/// Units are binary, matching how sizes are displayed.
+fn parse_size(input: &str) -> Result<u64> {
+    let input = input.trim();
+    let split = input.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        _ => return Err(anyhow::anyhow!("Invalid size '{}'. Use units B, KB, MB or GB (e.g. 50MB)", input)),
+    };
+    let value: f64 = number.trim().parse()
+        .map_err(|_| anyhow::anyhow!("Invalid size '{}'. Expected a number such as 50MB", input))?;
+    if !value.is_finite() || value < 0.0 {
+        return Err(anyhow::anyhow!("Invalid size '{}'", input));
+    }
+    
+    Ok((value * multiplier as f64) as u64)
+}
+
+/// Fail if the archive is larger than the competition's limit
+fn check_archive_size(archive_size: u64, limit: Option<u64>) -> Result<()> {
+    match limit {
+        Some(limit) if archive_size > limit => Err(anyhow::anyhow!(
+            "Archive is {} ({} bytes), over the {} ({} bytes) limit. Exclude more files or raise max_archive_bytes.",
+            format_bytes(archive_size),
+            archive_size,
+            format_bytes(limit),
+            limit
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// A node in the `optimus tree` listing
+#[derive(Serialize, Debug)]
+struct TreeNode {
+    name: String,
+    
+    /// Total size of the file, or of every file below a directory
+    size: u64,
+    
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    children: Vec<TreeNode>,
+    
+    #[serde(skip)]
+    is_dir: bool,
+}
+
+impl TreeNode {
+    fn new(name: &str, is_dir: bool) -> Self {
+        TreeNode { name: name.to_string(), size: 0, children: Vec::new(), is_dir }
+    }
+    
+    /// Insert a path below this node, adding its size to every directory on the way
+    fn insert(&mut self, components: &[String], size: u64, is_dir: bool) {
+        let Some((first, rest)) = components.split_first() else { return };
+        self.size += size;
+        
+        let index = match self.children.iter().position(|c| &c.name == first) {
+            Some(index) => index,
+            None => {
+                self.children.push(TreeNode::new(first, is_dir || !rest.is_empty()));
+                self.children.len() - 1
+            }
+        };
+        
+        let child = &mut self.children[index];
+        if rest.is_empty() {
+            child.size += size;
+        } else {
+            child.insert(rest, size, is_dir);
+        }
+    }
+    
+    /// Sort children by name at every level so the output is stable and diffable
+    fn sort(&mut self) {
+        self.children.sort_by(|a, b| a.name.cmp(&b.name));
+        for child in &mut self.children {
+            child.sort();
+        }
+    }
+    
+    /// Print the children of this node with `tree`-style connectors
+    fn print_children(&self, indent: &str) {
+        for (i, child) in self.children.iter().enumerate() {
+            let last = i == self.children.len() - 1;
+            let connector = if last { "└── " } else { "├── " };
+            
+            if child.is_dir {
+                println!("{}{}{}/", indent, connector, child.name);
+            } else {
+                println!("{}{}{} ({})", indent, connector, child.name, format_bytes(child.size));
+            }
+            
+            let child_indent = format!("{}{}", indent, if last { "    " } else { "│   " });
+            child.print_children(&child_indent);
+        }
+    }
+}
+
+/// Build a tree of the collected entries, returning it with file and directory counts
+fn build_tree(entries: &[ArchiveEntry]) -> Result<(TreeNode, usize, usize)> {
+    let mut root = TreeNode::new(".", true);
+    let mut files = 0;
+    let mut dirs = 0;
+    
+    for entry in entries {
+        let components: Vec<String> = Path::new(&entry.name).components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect();
+        
+        let size = if entry.is_dir {
+            dirs += 1;
+            0
+        } else {
+            files += 1;
+            std::fs::metadata(&entry.path)?.len()
+        };
+        
+        root.insert(&components, size, entry.is_dir);
+    }
+    
+    root.sort();
+    
+    Ok((root, files, dirs))
+}
+
+/// Print the collected entries as an indented tree
+fn print_tree(entries: &[ArchiveEntry]) -> Result<()> {
+    let (root, files, dirs) = build_tree(entries)?;
+    
+    println!(".");
+    root.print_children("");
+    println!();
+    println!("{} files, {} directories, {} total", files, dirs, format_bytes(root.size));
+    
+    Ok(())
+}
+
+/// Parse a human duration such as `90s`, `30m`, `1h30m` or `2d` (a bare number is seconds)
+fn parse_duration(input: &str) -> Result<Duration> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(anyhow::anyhow!("Empty duration"));
+    }
+    
+    if let Ok(secs) = input.parse::<u64>() {
+        return Ok(Duration::from_secs(secs));
+    }
+    
+    let mut total = 0u64;
+    let mut digits = String::new();
+    for c in input.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        
+        let multiplier = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86_400,
+            'w' => 604_800,
+            _ => return Err(anyhow::anyhow!("Invalid duration '{}'. Use units s, m, h, d or w (e.g. 30m, 1h30m)", input)),
+        };
+        
+        let value: u64 = digits.parse()
+            .map_err(|_| anyhow::anyhow!("Invalid duration '{}'. Expected a number before '{}'", input, c))?;
+        total = value.checked_mul(multiplier)
+            .and_then(|secs| total.checked_add(secs))
+            .ok_or_else(|| anyhow::anyhow!("Duration '{}' is too long", input))?;
+        digits.clear();
+    }
+    
+    if !digits.is_empty() {
+        return Err(anyhow::anyhow!("Invalid duration '{}'. Missing unit after {}", input, digits));
+    }
+    
+    Ok(Duration::from_secs(total))
+}
+
+/// Format a duration for display, e.g. `1h 5m` or `42s`
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, (secs % 3600) / 60, secs % 60);
+    
+    if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+/// Time left before another submission is allowed, based on the last one in history.
+///
+/// History only records successful submissions, so the latest entry for the competition
+/// is the last success.
+fn remaining_interval(history: &[HistoryEntry], competition_id: Option<&str>, min_interval: Duration, now: u64) -> Option<Duration> {
+    let last = history.iter()
+        .filter(|e| e.competition_id.as_deref() == competition_id)
+        .map(|e| e.timestamp)
+        .max()?;
+    
+    // A timestamp in the future (clock changes) counts as just now
+    let elapsed = Duration::from_secs(now.saturating_sub(last));
+    min_interval.checked_sub(elapsed).filter(|remaining| !remaining.is_zero())
+}
+
+/// Current UNIX time in seconds, or 0 if the clock is set before the epoch
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Difference between local and server clocks above which we warn
+const CLOCK_SKEW_WARN_SECS: u64 = 300;
+
+/// Days since the UNIX epoch for a proleptic Gregorian date (Howard Hinnant's algorithm)
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Parse an HTTP `Date` header (`Sun, 06 Nov 1994 08:49:37 GMT`) into a UNIX timestamp
+fn parse_http_date(date: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+    
+    let parts: Vec<&str> = date.split_whitespace().collect();
+    let [_, day, month, year, time, "GMT"] = parts.as_slice() else {
+        return None;
+    };
+    let day: i64 = day.parse().ok()?;
+    let month = MONTHS.iter().position(|m| m.eq_ignore_ascii_case(month))? as i64 + 1;
+    let year: i64 = year.parse().ok()?;
+    let mut hms = time.split(':').map(|part| part.parse::<u64>().ok());
+    let (hours, minutes, seconds) = (hms.next()??, hms.next()??, hms.next()??);
+    
+    let days = u64::try_from(days_from_civil(year, month, day)).ok()?;
+    Some(days * 86_400 + hours * 3600 + minutes * 60 + seconds)
+}
+
+/// Read the server's clock from a response's `Date` header, warning when ours is far off.
+///
+/// Returns the server time so callers can prefer it over the local clock.
+fn check_clock_skew(response: &Response) -> Option<u64> {
+    let header = response.headers().get(reqwest::header::DATE)?.to_str().ok()?;
+    let server_now = parse_http_date(header)?;
+    let skew = server_now.abs_diff(unix_now());
+    
+    if skew > CLOCK_SKEW_WARN_SECS {
+        warn(format!(
+            "System clock differs from {} by {}; time-based checks may be inaccurate",
+            response.url().host_str().unwrap_or("the server"),
+            format_duration(Duration::from_secs(skew))
+        ));
+    }
+    
+    Some(server_now)
+}
+
+/// Year, month and day in UTC of a UNIX timestamp
+fn civil_date(secs: u64) -> (i64, i64, i64) {
+    // Civil-from-days conversion (Howard Hinnant's algorithm)
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let doe = days.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Format a UNIX timestamp as a `YYYYMMDD` date in UTC
+fn format_date(secs: u64) -> String {
+    let (year, month, day) = civil_date(secs);
+    format!("{:04}{:02}{:02}", year, month, day)
+}
+
+/// A file's modification time as a zip timestamp (UTC), or the zip epoch when it has none
+/// or it falls outside the 1980-2107 range zip can store
+fn zip_modified_time(path: &Path) -> zip::DateTime {
+    let Some(secs) = std::fs::metadata(path).ok()
+        .and_then(|meta| meta.modified().ok())
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|since| since.as_secs())
+    else {
+        return zip::DateTime::default();
+    };
+    
+    let (year, month, day) = civil_date(secs);
+    let time = secs % 86_400;
+    u16::try_from(year).ok()
+        .and_then(|year| zip::DateTime::from_date_and_time(
+            year,
+            month as u8,
+            day as u8,
+            (time / 3600) as u8,
+            (time % 3600 / 60) as u8,
+            (time % 60) as u8,
+        ).ok())
+        .unwrap_or_default()
+}
+
+/// Values available to the `archive_name` template
+struct ArchiveNameContext<'a> {
+    dir: &'a str,
+    competition: Option<&'a str>,
+    timestamp: u64,
+    sha256: Option<&'a str>,
+    /// Format of the archive being named, which the extension must match
+    format: ArchiveFormat,
+}
+
+/// Render an `archive_name` template such as `{competition}-{date}.zip`
+fn render_archive_name(template: &str, context: &ArchiveNameContext) -> Result<String> {
+    let mut rendered = String::new();
+    let mut rest = template;
+    
+    while let Some(open) = rest.find('{') {
+        rendered.push_str(&rest[..open]);
+        let close = rest[open..].find('}')
+            .ok_or_else(|| anyhow::anyhow!("Unclosed placeholder in archive name template '{}'", template))?;
+        let placeholder = &rest[open + 1..open + close];
+        
+        let value = match placeholder {
+            "dir" => context.dir.to_string(),
+            "competition" => context.competition
+                .ok_or_else(|| anyhow::anyhow!(
+                    "Archive name template uses {{competition}} but no competition ID is set"
+                ))?
+                .to_string(),
+            "date" => format_date(context.timestamp),
+            "timestamp" => context.timestamp.to_string(),
+            "sha8" => context.sha256
+                .map(|hash| hash.chars().take(8).collect::<String>())
+                .ok_or_else(|| anyhow::anyhow!("Archive hash is not available for {{sha8}}"))?,
+            "version" => env!("CARGO_PKG_VERSION").to_string(),
+            other => {
+                return Err(anyhow::anyhow!(
+                    "Unknown placeholder '{{{}}}' in archive name template. Expected one of: dir, competition, date, timestamp, sha8, version",
+                    other
+                ));
+            }
+        };
+        
+        rendered.push_str(&value);
+        rest = &rest[open + close + 1..];
+    }
+    rendered.push_str(rest);
+    
+    validate_file_name(&rendered)?;
+    
+    // The upload's content type comes from the extension, so it has to match the contents
+    let suffixes = context.format.suffixes();
+    if !suffixes.iter().any(|suffix| rendered.to_lowercase().ends_with(suffix)) {
+        return Err(anyhow::anyhow!(
+            "Archive name '{}' must end in {} for the {} archive format",
+            rendered,
+            suffixes.join(" or "),
+            context.format.as_str()
+        ));
+    }
+    
+    Ok(rendered)
+}
+
+/// Ensure a name is a single, portable file name with no path components
+fn validate_file_name(name: &str) -> Result<()> {
+    const FORBIDDEN: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+    
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(anyhow::anyhow!("Archive name '{}' is not a valid file name", name));
+    }
+    
+    if name.chars().any(|c| c.is_control() || FORBIDDEN.contains(&c)) {
+        return Err(anyhow::anyhow!(
+            "Archive name '{}' contains characters that are not allowed in file names",
+            name
+        ));
+    }
+    
+    Ok(())
+}
+
+/// Rename a built archive according to the `archive_name` template, keeping it in place
+fn apply_archive_name(
+    zip_path: PathBuf,
+    template: &str,
+    root: &Path,
+    competition: Option<&str>,
+    format: ArchiveFormat,
+) -> Result<PathBuf> {
+    let dir_name = root_dir_name(root)?;
+    
+    // Only hash the archive when the template actually needs it
+    let sha256 = if template.contains("{sha8}") {
+        Some(sha256_file(&zip_path)?)
+    } else {
+        None
+    };
+    
+    let context = ArchiveNameContext {
+        dir: &dir_name,
+        competition,
+        timestamp: unix_now(),
+        sha256: sha256.as_deref(),
+        format,
+    };
+    
+    let file_name = render_archive_name(template, &context)?;
+    let renamed = zip_path.with_file_name(file_name);
+    
+    if renamed != zip_path {
+        std::fs::rename(&zip_path, &renamed)?;
+    }
+    
+    Ok(renamed)
+}
+
+/// Form fields and encoding for an archive upload
+#[derive(Clone, Copy)]
+struct UploadOptions<'a> {
+    competition_id: Option<&'a str>,
+    /// Extra text fields for optional features
+    fields: &'a [(&'static str, String)],
+    /// Overrides the content type derived from the archive's extension
+    content_type: Option<&'a str>,
+    /// Hash the archive with this algorithm while it uploads
+    parallel_hash: Option<HashAlgo>,
+    /// Leave the archive on disk after a successful upload
+    keep_archive: bool,
+    /// Draw a progress bar while uploading
+    show_progress: bool,
+    /// Upload in chunks to `/submit/chunk` and `/submit/complete`, because the server supports it
+    chunked: bool,
+    /// Chunks in flight at once during a chunked upload
+    concurrency: usize,
+}
+
+/// Incremental hasher for any supported checksum algorithm
+enum StreamingHasher {
+    Sha256(Sha256),
+    Sha512(Sha512),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl StreamingHasher {
+    fn new(algo: HashAlgo) -> Self {
+        match algo {
+            HashAlgo::Sha256 => StreamingHasher::Sha256(Sha256::new()),
+            HashAlgo::Sha512 => StreamingHasher::Sha512(Sha512::new()),
+            HashAlgo::Blake3 => StreamingHasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+    
+    fn update(&mut self, bytes: &[u8]) {
+        match self {
+            StreamingHasher::Sha256(hasher) => hasher.update(bytes),
+            StreamingHasher::Sha512(hasher) => hasher.update(bytes),
+            StreamingHasher::Blake3(hasher) => {
+                hasher.update(bytes);
+            }
+        }
+    }
+    
+    fn finalize_hex(self) -> String {
+        match self {
+            StreamingHasher::Sha256(hasher) => format!("{:x}", hasher.finalize()),
+            StreamingHasher::Sha512(hasher) => format!("{:x}", hasher.finalize()),
+            StreamingHasher::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+        }
+    }
+}
+
+/// A hasher shared between the reader feeding it and whoever finalizes it
+type SharedHasher = Arc<Mutex<Option<StreamingHasher>>>;
+
+/// Reader that feeds everything it reads into shared hashers
+struct HashingReader<R> {
+    inner: R,
+    hashers: Vec<SharedHasher>,
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        for hasher in &self.hashers {
+            if let Some(hasher) = hasher.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+                hasher.update(&buf[..read]);
+            }
+        }
+        Ok(read)
+    }
+}
+
+/// Multipart body for a checksum that is only finalized when the server starts reading it.
+///
+/// Parts are sent in order, so placed after the file this sees the complete archive. Two parts
+/// may share a hasher and digest: the first finalizes it and the second sends the same value.
+struct DeferredChecksum {
+    hasher: SharedHasher,
+    /// Where the finished hex digest is left for reporting
+    digest: Arc<Mutex<Option<String>>>,
+    pending: Option<std::io::Cursor<Vec<u8>>>,
+}
+
+impl Read for DeferredChecksum {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pending.is_none() {
+            let hasher = self.hasher.lock().unwrap_or_else(|e| e.into_inner()).take();
+            let mut digest = self.digest.lock().unwrap_or_else(|e| e.into_inner());
+            let hex = match hasher {
+                Some(hasher) => digest.insert(hasher.finalize_hex()).clone(),
+                None => digest.clone()
+                    .ok_or_else(|| std::io::Error::other("Checksum was already finalized"))?,
+            };
+            self.pending = Some(std::io::Cursor::new(hex.into_bytes()));
+        }
+        
+        self.pending.as_mut().map_or(Ok(0), |pending| pending.read(buf))
+    }
+}
+
+/// MIME type for an archive, based on its extension
+fn archive_content_type(path: &Path) -> &'static str {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+    if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        "application/gzip"
+    } else if name.ends_with(".zip") {
+        "application/zip"
+    } else {
+        "application/octet-stream"
+    }
+}
+
+/// Check that a `--content-type` value is a well-formed `type/subtype` MIME type
+fn validate_content_type(content_type: &str) -> Result<()> {
+    // Parameters such as `; charset=...` are allowed after the essence
+    let essence = content_type.split(';').next().unwrap_or_default().trim();
+    let is_token = |part: &str| !part.is_empty()
+        && part.chars().all(|c| c.is_ascii_alphanumeric() || "!#$&-^_.+".contains(c));
+    
+    match essence.split_once('/') {
+        Some((kind, subtype)) if is_token(kind) && is_token(subtype) => Ok(()),
+        _ => Err(anyhow::anyhow!("Invalid content type '{}'. Expected a MIME type such as application/zip", content_type)),
+    }
+}
+
+/// Send the zip file to the endpoint
+fn send_zip_to_endpoint(
+    client: &Client,
+    zip_path: &Path,
+    config: &SubmissionConfig,
+    submit_url: &str,
+    upload: &UploadOptions,
+) -> Result<SubmitResult> {
+    let UploadOptions { competition_id, fields, content_type, parallel_hash, keep_archive, show_progress, chunked, .. } = *upload;
+    
+    let length = std::fs::metadata(zip_path)
+        .with_context(|| format!("Failed to read {}", zip_path.display()))?
+        .len();
+    status!("📦 Sending zip file ({}) to server: {}", format_bytes(length), submit_url);
+    
+    let file_name = zip_path.file_name()
+        .context("Failed to get zip file name")?
+        .to_string_lossy();
+    
+    // Filled in by the checksum part of whichever attempt reads the whole archive
+    let digest = Arc::new(Mutex::new(None));
+    // A SHA-256 kept alongside a parallel hash in another algorithm, sent as the `sha256` field
+    // and kept for the history record. Each attempt replaces it, so it ends up describing the
+    // attempt that went through.
+    let mut sha256_hasher: Option<SharedHasher> = None;
+    let sha256_digest = Arc::new(Mutex::new(None));
+    let mut chunked_sha256 = None;
+    
+    let response = if chunked && length > UPLOAD_CHUNK_BYTES {
+        let chunks = upload_chunks(client, zip_path, config, submit_url, length, upload)?;
+        if let Some(checksum) = &chunks.digest {
+            *digest.lock().unwrap_or_else(|e| e.into_inner()) = Some(checksum.clone());
+        }
+        chunked_sha256 = chunks.sha256.clone();
+        
+        // The server joins the chunks into the submission, taking the usual form fields
+        let complete_url = format!("{}/complete", submit_url);
+        send_with_retry(&config.retry_on, false, "complete", || {
+            let mut form = multipart::Form::new()
+                .text("upload_id", chunks.upload_id.clone())
+                .text("total", chunks.total.to_string())
+                .text("file_name", file_name.to_string())
+                .text("content_type", content_type.unwrap_or_else(|| archive_content_type(zip_path)).to_string());
+            if let Some(comp_id) = competition_id {
+                form = form.text("competition", comp_id.to_string());
+            }
+            for (name, value) in fields {
+                form = form.text(*name, value.clone());
+            }
+            if let (Some(algo), Some(checksum)) = (parallel_hash, &chunks.digest) {
+                let sha256 = chunks.sha256.as_ref().unwrap_or(checksum);
+                form = form
+                    .text("sha256", sha256.clone())
+                    .text("checksum", checksum.clone())
+                    .text("checksum_algo", algo.as_str());
+            }
+            
+            let request = client.post(&complete_url)
+                .timeout(Duration::from_secs(config.upload_timeout_secs))
+                .multipart(form);
+            Ok(authorize(request, config)?.send()?)
+        })?
+    } else {
+        // Sending consumes the streamed file, so the form is rebuilt for every attempt
+        send_with_retry(&config.retry_on, false, "submit", || {
+            // The archive is streamed from disk rather than read into memory. With a parallel
+            // hash it also passes through the hasher on its way out.
+            let file = File::open(zip_path)
+                .with_context(|| format!("Failed to open {}", zip_path.display()))?;
+            
+            // The bar advances as the request body reads the archive
+            let progress = if show_progress {
+                let bar = ProgressBar::new(length);
+                bar.set_style(ProgressStyle::with_template("{bar:40} {bytes}/{total_bytes} ({eta})")?);
+                bar
+            } else {
+                ProgressBar::hidden()
+            };
+            
+            let mut hash_state = None;
+            let file_part = match parallel_hash {
+                Some(algo) => {
+                    let hasher = Arc::new(Mutex::new(Some(StreamingHasher::new(algo))));
+                    let mut hashers = vec![Arc::clone(&hasher)];
+                    sha256_hasher = (algo != HashAlgo::Sha256)
+                        .then(|| Arc::new(Mutex::new(Some(StreamingHasher::new(HashAlgo::Sha256)))));
+                    hashers.extend(sha256_hasher.iter().cloned());
+                    let reader = HashingReader { inner: file, hashers };
+                    hash_state = Some((algo, hasher));
+                    multipart::Part::reader_with_length(progress.wrap_read(reader), length)
+                }
+                None => multipart::Part::reader_with_length(progress.wrap_read(BufReader::new(file)), length),
+            };
+            
+            // Create a multipart form with the zip file
+            let mut form = multipart::Form::new()
+                .part("file", file_part
+                    .file_name(file_name.to_string())
+                    .mime_str(content_type.unwrap_or_else(|| archive_content_type(zip_path)))?);
+            
+            // Add competition_id if available
+            if let Some(comp_id) = competition_id {
+                form = form.text("competition", comp_id.to_string());
+            }
+            
+            // Add any extra text fields for optional features
+            for (name, value) in fields {
+                form = form.text(*name, value.clone());
+            }
+            
+            // The checksum parts come after the file, so they are only read once the whole
+            // archive has passed through the hashers. A SHA-256 checksum doubles as `sha256`.
+            if let Some((algo, hasher)) = hash_state {
+                let sha256 = match &sha256_hasher {
+                    Some(sha256_hasher) => DeferredChecksum {
+                        hasher: Arc::clone(sha256_hasher),
+                        digest: Arc::clone(&sha256_digest),
+                        pending: None,
+                    },
+                    None => DeferredChecksum { hasher: Arc::clone(&hasher), digest: Arc::clone(&digest), pending: None },
+                };
+                let checksum = DeferredChecksum { hasher, digest: Arc::clone(&digest), pending: None };
+                form = form
+                    .part("checksum", multipart::Part::reader(checksum))
+                    .part("sha256", multipart::Part::reader(sha256))
+                    .text("checksum_algo", algo.as_str());
+            }
+            
+            // Send the POST request with the configured credentials
+            let request = client.post(submit_url)
+                .timeout(Duration::from_secs(config.upload_timeout_secs))
+                .multipart(form);
+            let sent = authorize(request, config)?.send();
+            progress.finish_and_clear();
+            Ok(sent?)
+        })?
+    };
+    let status = response.status();
+    let body = response.text().unwrap_or_default();
+    log_record("submit", submit_url, status.as_u16(), &body);
+    
+    // A conflict on a labelled submission means the label is already taken
+    let conflicting_label = fields.iter()
+        .find(|(name, _)| *name == "label")
+        .map(|(_, label)| label)
+        .filter(|_| status == reqwest::StatusCode::CONFLICT);
+    
+    // Check if the request was successful
+    if status.is_success() {
+        status!("✅ Successfully sent the zip file to the server!");
+        status!("   Response: {}", body);
+        if let (Some(algo), Some(digest)) = (parallel_hash, digest.lock().unwrap_or_else(|e| e.into_inner()).as_ref()) {
+            status!("🔐 Archive {} (computed during upload): {}", algo.as_str(), digest);
+        }
+    } else if let Some(label) = conflicting_label {
+        return Err(anyhow::anyhow!(
+            "A submission labelled '{}' already exists. Re-run with --overwrite-label to replace it. Server said: {}",
+            label,
+            body
+        ));
+    } else {
+        return Err(anyhow::anyhow!(
+            "Failed to send zip file to endpoint. Status: {}, Body: {}",
+            status,
+            body
+        ));
+    }
+    
+    // Clean up the temporary zip file
+    if !keep_archive {
+        std::fs::remove_file(zip_path)?;
+    }
+    
+    // Servers that answer with JSON may identify the new submission
+    let submission_id = serde_json::from_str::<serde_json::Value>(&body).ok().and_then(|value| {
+        match value.get("submission_id").or_else(|| value.get("id"))? {
+            serde_json::Value::String(id) => Some(id.clone()),
+            serde_json::Value::Number(id) => Some(id.to_string()),
+            _ => None,
+        }
+    });
+    
+    // The archive's SHA-256 as it was streamed, so a parallel hash never needs a second read
+    let archive_sha256 = match parallel_hash {
+        Some(HashAlgo::Sha256) => digest.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+        Some(_) => chunked_sha256.or_else(|| sha256_digest.lock().unwrap_or_else(|e| e.into_inner()).clone()),
+        None => None,
+    };
+    
+    Ok(SubmitResult { status: status.as_u16(), submission_id, archive_sha256 })
+}
+
+/// Size of each piece of a chunked upload
+const UPLOAD_CHUNK_BYTES: u64 = 8 * 1024 * 1024;
+
+/// The staged pieces of a chunked upload, ready for `/submit/complete`
+#[derive(Debug)]
+struct UploadedChunks {
+    upload_id: String,
+    total: u64,
+    /// Checksum of the whole archive, when one was requested
+    digest: Option<String>,
+    /// SHA-256 of the whole archive, when the requested checksum used another algorithm
+    sha256: Option<String>,
+}
+
+/// Send the archive in `UPLOAD_CHUNK_BYTES` pieces to `<submit_url>/chunk`, `concurrency` at a time.
+///
+/// Chunks are read and hashed in order on this thread and handed to the senders through a
+/// queue of `concurrency` slots, so at most twice that many chunks are held in memory. Each
+/// chunk is retried on its own, so a dropped connection only repeats that chunk instead of
+/// the whole upload. Every chunk is attempted; the ones that still fail are reported together.
+fn upload_chunks(
+    client: &Client,
+    zip_path: &Path,
+    config: &SubmissionConfig,
+    submit_url: &str,
+    length: u64,
+    upload: &UploadOptions,
+) -> Result<UploadedChunks> {
+    let chunk_url = format!("{}/chunk", submit_url);
+    let total = length.div_ceil(UPLOAD_CHUNK_BYTES);
+    let concurrency = upload.concurrency.max(1);
+    let hash = upload.parallel_hash;
+    
+    // Identifies this upload's chunks to the server; unique enough per machine and moment
+    let mut id_hasher = Sha256::new();
+    id_hasher.update(format!("{:?}\0{}\0{}", SystemTime::now(), std::process::id(), zip_path.display()));
+    let upload_id = format!("{:x}", id_hasher.finalize())[..32].to_string();
+    status!(
+        "🧩 Uploading in {} chunks of up to {} ({} at a time)",
+        total,
+        format_bytes(UPLOAD_CHUNK_BYTES),
+        concurrency.min(total as usize)
+    );
+    
+    let progress = if upload.show_progress {
+        let bar = ProgressBar::new(length);
+        bar.set_style(ProgressStyle::with_template("{bar:40} {bytes}/{total_bytes} ({eta})")?);
+        bar
+    } else {
+        ProgressBar::hidden()
+    };
+    
+    let mut hasher = hash.map(StreamingHasher::new);
+    let mut sha256_hasher = hash
+        .filter(|algo| *algo != HashAlgo::Sha256)
+        .map(|_| StreamingHasher::new(HashAlgo::Sha256));
+    let mut file = File::open(zip_path)
+        .with_context(|| format!("Failed to open {}", zip_path.display()))?;
+    let failures = Mutex::new(Vec::new());
+    let (sender, receiver) = mpsc::sync_channel::<(u64, Vec<u8>)>(concurrency);
+    let receiver = Mutex::new(receiver);
+    
+    thread::scope(|scope| -> Result<()> {
+        for _ in 0..concurrency {
+            let (receiver, failures, progress) = (&receiver, &failures, &progress);
+            let (chunk_url, upload_id) = (chunk_url.as_str(), upload_id.as_str());
+            scope.spawn(move || loop {
+                let next = receiver.lock().unwrap_or_else(|e| e.into_inner()).recv();
+                let Ok((index, chunk)) = next else { break };
+                match upload_chunk(client, config, chunk_url, upload_id, index, total, &chunk) {
+                    Ok(()) => progress.inc(chunk.len() as u64),
+                    Err(err) => failures.lock().unwrap_or_else(|e| e.into_inner()).push((index, err)),
+                }
+            });
+        }
+        
+        // Dropping the sender when reading stops, even on an error, lets the senders finish
+        let sender = sender;
+        for index in 0..total {
+            let mut chunk = Vec::with_capacity(UPLOAD_CHUNK_BYTES as usize);
+            (&mut file).take(UPLOAD_CHUNK_BYTES).read_to_end(&mut chunk)?;
+            for hasher in hasher.iter_mut().chain(sha256_hasher.iter_mut()) {
+                hasher.update(&chunk);
+            }
+            if sender.send((index, chunk)).is_err() {
+                break;
+            }
+        }
+        
+        Ok(())
+    })?;
+    progress.finish_and_clear();
+    
+    let mut failures = failures.into_inner().unwrap_or_else(|e| e.into_inner());
+    if !failures.is_empty() {
+        failures.sort_by_key(|(index, _)| *index);
+        let details: Vec<String> = failures.iter().map(|(_, err)| format!("  {:#}", err)).collect();
+        return Err(anyhow::anyhow!(
+            "Failed to upload {} of {} chunks:\n{}",
+            failures.len(),
+            total,
+            details.join("\n")
+        ));
+    }
+    
+    Ok(UploadedChunks {
+        upload_id,
+        total,
+        digest: hasher.map(StreamingHasher::finalize_hex),
+        sha256: sha256_hasher.map(StreamingHasher::finalize_hex),
+    })
+}
+
+/// Send one chunk of a chunked upload, retrying it on its own.
+///
+/// The `Idempotency-Key` header (`<upload_id>-<index>`) lets the server recognise a resent
+/// chunk it already stored, which is why timeouts are safe to retry here.
+fn upload_chunk(
+    client: &Client,
+    config: &SubmissionConfig,
+    chunk_url: &str,
+    upload_id: &str,
+    index: u64,
+    total: u64,
+    chunk: &[u8],
+) -> Result<()> {
+    let what = format!("chunk {}/{}", index + 1, total);
+    let response = send_with_retry(&config.retry_on, true, &what, || {
+        let form = multipart::Form::new()
+            .text("upload_id", upload_id.to_string())
+            .text("index", index.to_string())
+            .text("total", total.to_string())
+            .part("chunk", multipart::Part::bytes(chunk.to_vec()).mime_str("application/octet-stream")?);
+        let request = client.post(chunk_url)
+            .header("Idempotency-Key", format!("{}-{}", upload_id, index))
+            .timeout(Duration::from_secs(config.upload_timeout_secs))
+            .multipart(form);
+        Ok(authorize(request, config)?.send()?)
+    }).with_context(|| format!("{} could not be sent", what))?;
+    
+    let status = response.status();
+    if !status.is_success() {
+        return Err(anyhow::anyhow!(
+            "{} was rejected. Status: {}, Body: {}",
+            what,
+            status,
+            response.text().unwrap_or_default()
+        ));
+    }
+    Ok(())
+}
+
+/// What the server said about an accepted upload
+struct SubmitResult {
+    status: u16,
+    /// From a JSON response's `submission_id` (or `id`) field
+    submission_id: Option<String>,
+    /// SHA-256 of the archive computed while it uploaded, with a parallel hash
+    archive_sha256: Option<String>,
+}
+
+/// Move a built archive to `output`, or into it when `output` is a directory.
+///
+/// Falls back to copying when the two are on different filesystems.
+fn save_archive(zip_path: &Path, output: &Path) -> Result<PathBuf> {
+    let destination = match zip_path.file_name() {
+        Some(name) if output.is_dir() => output.join(name),
+        _ => output.to_path_buf(),
+    };
+    
+    if std::fs::rename(zip_path, &destination).is_err() {
+        std::fs::copy(zip_path, &destination)
+            .with_context(|| format!("Failed to write the archive to {}", destination.display()))?;
+        std::fs::remove_file(zip_path)?;
+    }
+    Ok(destination)
+}
+
+/// Longest submission note accepted, in characters
+const MAX_NOTE_LEN: usize = 280;
+
+/// Check that a submission note is short and free of control characters
+fn validate_note(note: &str) -> Result<()> {
+    let len = note.chars().count();
+    if len > MAX_NOTE_LEN {
+        return Err(anyhow::anyhow!("Note is {} characters long; the limit is {}", len, MAX_NOTE_LEN));
+    }
+    if note.chars().any(char::is_control) {
+        return Err(anyhow::anyhow!("Note must be a single line without control characters"));
+    }
+    Ok(())
+}
+
+/// Longest submission label accepted
+const MAX_LABEL_LEN: usize = 64;
+
+/// Check that a label is short and made of letters, digits and dashes
+fn validate_label(label: &str) -> Result<()> {
+    if label.is_empty() || label.len() > MAX_LABEL_LEN {
+        return Err(anyhow::anyhow!("Label must be between 1 and {} characters", MAX_LABEL_LEN));
+    }
+    if !label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(anyhow::anyhow!("Invalid label '{}'. Use only letters, digits and dashes", label));
+    }
+    Ok(())
+}
+
+/// Interactively ask for an optional submission note, re-prompting until it is valid
+fn prompt_note() -> Result<Option<String>> {
+    let note: String = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("Submission note (optional)")
+        .allow_empty(true)
+        .validate_with(|input: &String| validate_note(input).map_err(|err| err.to_string()))
+        .interact_text()?;
+    
+    let note = note.trim();
+    Ok((!note.is_empty()).then(|| note.to_string()))
+}
+
+/// Directory holding Optimus caches (e.g. `~/.cache/optimus` on Linux)
+fn cache_dir() -> Result<PathBuf> {
+    let base = dirs::cache_dir().context("Failed to locate the platform cache directory")?;
+    Ok(base.join("optimus"))
+}
+
+/// Total size of the files under `path`, or 0 if it doesn't exist
+fn dir_size(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|meta| meta.is_file())
+        .map(|meta| meta.len())
+        .sum()
+}
+
+/// Print the cache location and the size of each cache
+fn print_cache_info() -> Result<()> {
+    println!("📁 Cache directory: {}", cache_dir()?.display());
+    if let Some(state_dir) = upload_rate_path()?.parent() {
+        println!("📁 State directory: {}", state_dir.display());
+    }
+    
+    let mut total = 0;
+    for kind in CacheTarget::All.kinds() {
+        let size: u64 = kind.paths()?.iter().map(|path| dir_size(path)).sum();
+        total += size;
+        println!("   {:<12} {}", kind.dir_name(), format_bytes(size));
+    }
+    println!("   {:<12} {}", "total", format_bytes(total));
+    
+    Ok(())
+}
+
+/// Remove the selected caches, ignoring ones that don't exist
+fn clear_cache(target: CacheTarget) -> Result<()> {
+    let mut freed = 0;
+    
+    for path in target.paths()? {
+        if !path.exists() {
+            continue;
+        }
+        
+        freed += dir_size(&path);
+        let removed = if path.is_dir() {
+            std::fs::remove_dir_all(&path)
+        } else {
+            std::fs::remove_file(&path)
+        };
+        removed.with_context(|| format!("Failed to remove {}", path.display()))?;
+    }
+    
+    println!("🧹 Cleared cache, freed {}", format_bytes(freed));
+    Ok(())
+}
+
+/// Cache file for a competition's server-enforced excludes
+fn server_excludes_cache_path(competition_id: Option<&str>) -> Result<PathBuf> {
+    // Keep the competition ID from escaping the cache directory
+    let key: String = competition_id.unwrap_or("default")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    Ok(cache_dir()?.join(CacheTarget::Check.dir_name()).join(format!("server-excludes-{}.json", key)))
+}
+
+/// Remember the server's excludes for use when the check is skipped
+fn save_server_excludes(competition_id: Option<&str>, excludes: &[String]) {
+    let cached = CachedServerExcludes {
+        excludes: excludes.to_vec(),
+        fetched_at: unix_now(),
+    };
+    let saved = server_excludes_cache_path(competition_id).and_then(|path| {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(&cached)?)?;
+        Ok(())
+    });
+    if let Err(err) = saved {
+        warn(format!("Failed to cache server excludes: {}", err));
+    }
+}
+
+/// The last server excludes cached for a competition, if any
+fn load_server_excludes(competition_id: Option<&str>) -> Option<CachedServerExcludes> {
+    let contents = std::fs::read_to_string(server_excludes_cache_path(competition_id).ok()?).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+/// Cache files describing the last successful submission for a competition
+struct LastSubmissionPaths {
+    manifest: PathBuf,
+    archive: PathBuf,
+    pending_archive: PathBuf,
+}
+
+fn last_submission_paths(competition_id: Option<&str>) -> Result<LastSubmissionPaths> {
+    let key: String = competition_id.unwrap_or("default")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    let dir = cache_dir()?.join(CacheTarget::Submissions.dir_name());
+    
+    Ok(LastSubmissionPaths {
+        manifest: dir.join(format!("{}.json", key)),
+        archive: dir.join(format!("{}.zip", key)),
+        pending_archive: dir.join(format!("{}.pending.zip", key)),
+    })
+}
+
+/// Copy the archive about to be uploaded, to be promoted once the upload succeeds
+fn stash_pending_archive(competition_id: Option<&str>, zip_path: &Path) {
+    let stashed = last_submission_paths(competition_id).and_then(|paths| {
+        if let Some(dir) = paths.pending_archive.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::copy(zip_path, &paths.pending_archive)?;
+        Ok(())
+    });
+    if let Err(err) = stashed {
+        warn(format!("Failed to keep a copy of the archive for review: {}", err));
+    }
+}
+
+/// Record what was just submitted as the baseline for the next `--review`
+fn save_last_submission(competition_id: Option<&str>, files: &[ManifestEntry], promote_archive: bool) {
+    let saved = last_submission_paths(competition_id).and_then(|paths| {
+        if let Some(dir) = paths.manifest.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&paths.manifest, serde_json::to_string(files)?)?;
+        
+        // Drop any older copy so diffs never compare against the wrong submission
+        if promote_archive && paths.pending_archive.exists() {
+            std::fs::rename(&paths.pending_archive, &paths.archive)?;
+        } else if paths.archive.exists() {
+            std::fs::remove_file(&paths.archive)?;
+        }
+        Ok(())
+    });
+    if let Err(err) = saved {
+        warn(format!("Failed to record the submission for future reviews: {}", err));
+    }
+}
+
+/// Read an archive member as UTF-8 text, or `None` if it is missing or binary.
+///
+/// Zip files and gzipped tarballs are told apart by their contents, since the copy kept for
+/// review is always named `.zip`.
+fn read_archive_text(archive_path: &Path, name: &str) -> Option<String> {
+    let mut file = File::open(archive_path).ok()?;
+    let mut magic = [0u8; 2];
+    let is_gzip = file.read_exact(&mut magic).is_ok() && magic == [0x1f, 0x8b];
+    file.rewind().ok()?;
+    
+    let mut contents = String::new();
+    if is_gzip {
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(file));
+        let mut entry = archive.entries().ok()?
+            .filter_map(|entry| entry.ok())
+            .find(|entry| entry.path().is_ok_and(|path| path == Path::new(name)))?;
+        entry.read_to_string(&mut contents).ok()?;
+    } else {
+        let mut archive = zip::ZipArchive::new(file).ok()?;
+        archive.by_name(name).ok()?.read_to_string(&mut contents).ok()?;
+    }
+    Some(contents)
+}
+
+/// Show files changed since the last submission and ask whether to go ahead.
+///
+/// Modified text files can be diffed against the copy kept from the last reviewed submission.
+fn review_changes(competition_id: Option<&str>, current: &[ManifestEntry], zip_path: &Path) -> Result<bool> {
+    let paths = last_submission_paths(competition_id)?;
+    let previous: Vec<ManifestEntry> = match std::fs::read_to_string(&paths.manifest) {
+        Ok(contents) => serde_json::from_str(&contents)?,
+        Err(_) => {
+            println!("ℹ️ No previous submission recorded for review; all {} files are new.", current.len());
+            return Ok(Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt("Proceed with submission?")
+                .default(true)
+                .interact()?);
+        }
+    };
+    
+    let previous_hashes: HashMap<&str, &str> = previous.iter()
+        .map(|f| (f.path.as_str(), f.sha256.as_str()))
+        .collect();
+    let current_paths: HashSet<&str> = current.iter().map(|f| f.path.as_str()).collect();
+    
+    // (marker, path, diffable)
+    let mut changes: Vec<(char, &str, bool)> = Vec::new();
+    for file in current {
+        match previous_hashes.get(file.path.as_str()) {
+            None => changes.push(('A', file.path.as_str(), false)),
+            Some(hash) if *hash != file.sha256 => changes.push(('M', file.path.as_str(), true)),
+            Some(_) => {}
+        }
+    }
+    for file in previous.iter().filter(|f| !current_paths.contains(f.path.as_str())) {
+        changes.push(('D', file.path.as_str(), false));
+    }
+    changes.sort_by(|a, b| a.1.cmp(b.1));
+    
+    let count = |marker: char| changes.iter().filter(|c| c.0 == marker).count();
+    println!(
+        "🔎 Since the last submission: {} added, {} modified, {} removed",
+        count('A'),
+        count('M'),
+        count('D')
+    );
+    
+    let mut items = vec!["Proceed with submission".to_string(), "Abort".to_string()];
+    items.extend(changes.iter().map(|(marker, path, _)| format!("{} {}", marker, path)));
+    
+    loop {
+        let choice = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Review changes (select a modified file to see its diff)")
+            .items(&items)
+            .default(0)
+            .interact()?;
+        
+        match choice {
+            0 => return Ok(true),
+            1 => return Ok(false),
+            _ => {
+                let (_, path, diffable) = changes[choice - 2];
+                if !diffable {
+                    continue;
+                }
+                
+                let old = read_archive_text(&paths.archive, path);
+                let new = read_archive_text(zip_path, path);
+                match (old, new) {
+                    (Some(old), Some(new)) => {
+                        let diff = similar::TextDiff::from_lines(&old, &new);
+                        print!("{}", diff.unified_diff().header(&format!("a/{}", path), &format!("b/{}", path)));
+                    }
+                    _ => println!("ℹ️ No text diff available for {} (binary, or no cached copy of the last submission)", path),
+                }
+            }
+        }
+    }
+}
+
+/// Location of the local submission history file
+fn history_path() -> Result<PathBuf> {
+    let home = dirs::home_dir().context("Failed to locate home directory")?;
+    Ok(home.join(".optimus").join("history.jsonl"))
+}
+
+/// Read all submission history records, oldest first
+fn read_history() -> Result<Vec<HistoryEntry>> {
+    let path = history_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let reader = BufReader::new(File::open(&path)?);
+    let mut entries = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        // Skip malformed records rather than failing the whole read
+        if let Ok(entry) = serde_json::from_str::<HistoryEntry>(&line) {
+            entries.push(entry);
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Drop records older than `cutoff` (a UNIX timestamp), then all but the newest `keep`
+fn prune_history(mut entries: Vec<HistoryEntry>, keep: Option<usize>, cutoff: Option<u64>) -> Vec<HistoryEntry> {
+    if let Some(cutoff) = cutoff {
+        entries.retain(|e| e.timestamp >= cutoff);
+    }
+    if let Some(keep) = keep {
+        let excess = entries.len().saturating_sub(keep);
+        entries.drain(..excess);
+    }
+    entries
+}
+
+/// Replace the history file with `entries`.
+///
+/// Written to a temporary file next to it and renamed into place, so a crash mid-write
+/// leaves the previous history intact.
+fn write_history(entries: &[HistoryEntry]) -> Result<()> {
+    let path = history_path()?;
+    let dir = path.parent().context("History path has no parent directory")?;
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+    
+    let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+    for entry in entries {
+        writeln!(temp, "{}", serde_json::to_string(entry)?)?;
+    }
+    temp.as_file().sync_all()?;
+    temp.persist(&path)
+        .with_context(|| format!("Failed to replace {}", path.display()))?;
+    Ok(())
+}
+
+/// Append a record to the history, pruning it to `history_limit`.
+///
+/// Does nothing when `save_history` is off. Failures only warn, since the submission
+/// itself has already succeeded.
+fn record_history(preferences: &Preferences, entry: HistoryEntry) {
+    if !preferences.save_history {
+        return;
+    }
+    
+    let result = read_history().and_then(|mut entries| {
+        entries.push(entry);
+        write_history(&prune_history(entries, Some(preferences.history_limit), None))
+    });
+    if let Err(err) = result {
+        warn(format!("Failed to record the submission in history: {:#}", err));
+    }
+}
+
+/// Print the most recent `limit` history records as a table, oldest first
+fn print_history(entries: &[HistoryEntry], limit: usize) {
+    if entries.is_empty() {
+        println!("No submissions recorded yet.");
+        return;
+    }
+    
+    println!(
+        "{:<16}  {:<24}  {:<6}  {:>10}  {:>4}  {:<20}  LABEL",
+        "DATE (UTC)", "COMPETITION", "FORMAT", "SIZE", "HTTP", "SUBMISSION"
+    );
+    for entry in &entries[entries.len().saturating_sub(limit)..] {
+        let date = format_date(entry.timestamp);
+        println!(
+            "{:<16}  {:<24}  {:<6}  {:>10}  {:>4}  {:<20}  {}",
+            format!(
+                "{}-{}-{} {:02}:{:02}",
+                &date[..4], &date[4..6], &date[6..],
+                entry.timestamp % 86_400 / 3600,
+                entry.timestamp % 3600 / 60
+            ),
+            entry.competition_id.as_deref().unwrap_or("-"),
+            entry.format.as_deref().unwrap_or("-"),
+            entry.archive_size.map(format_bytes).unwrap_or_else(|| "-".to_string()),
+            entry.server_status.map(|status| status.to_string()).unwrap_or_else(|| "-".to_string()),
+            entry.submission_id.as_deref().unwrap_or("-"),
+            entry.label.as_deref().unwrap_or(""),
+        );
+    }
+}
+
+/// Upload throughput measured on a previous submission
+#[derive(Deserialize, Serialize, Debug)]
+struct UploadRate {
+    bytes_per_sec: f64,
+    timestamp: u64,
+}
+
+/// How long a remembered upload rate is trusted before probing again
+const UPLOAD_RATE_MAX_AGE_SECS: u64 = 7 * 86_400;
+
+/// Size of the payload sent when probing upload bandwidth
+const BANDWIDTH_PROBE_BYTES: usize = 256 * 1024;
+
+/// Location of the remembered upload rate
+fn upload_rate_path() -> Result<PathBuf> {
+    let home = dirs::home_dir().context("Failed to locate home directory")?;
+    Ok(home.join(".optimus").join("upload_rate.json"))
+}
+
+/// Remember the throughput of a completed upload for later estimates
+fn record_upload_rate(bytes: u64, elapsed: Duration) {
+    // Tiny uploads are dominated by latency and say little about bandwidth
+    if bytes < BANDWIDTH_PROBE_BYTES as u64 || elapsed.is_zero() {
+        return;
+    }
+    
+    let rate = UploadRate {
+        bytes_per_sec: bytes as f64 / elapsed.as_secs_f64(),
+        timestamp: unix_now(),
+    };
+    let saved = upload_rate_path().and_then(|path| {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, serde_json::to_string(&rate)?)?;
+        Ok(())
+    });
+    if let Err(err) = saved {
+        warn(format!("Failed to remember upload rate: {}", err));
+    }
+}
+
+/// Estimate upload throughput in bytes per second.
+///
+/// Uses a recent remembered rate when there is one, otherwise times a small upload to the
+/// server. Either way the figure is only a rough guide.
+fn upload_rate_estimate(client: &Client, server_url: &str, config: &SubmissionConfig) -> Option<f64> {
+    let remembered = upload_rate_path().ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str::<UploadRate>(&contents).ok())
+        .filter(|rate| unix_now().saturating_sub(rate.timestamp) <= UPLOAD_RATE_MAX_AGE_SECS);
+    if let Some(rate) = remembered {
+        return Some(rate.bytes_per_sec);
+    }
+    
+    status!("📶 Measuring upload speed...");
+    let start = Instant::now();
+    let probe_url = api_url(server_url, config, "/bandwidth-probe").ok()?;
+    let probe = client.post(probe_url)
+        .timeout(Duration::from_secs(10))
+        .body(vec![0u8; BANDWIDTH_PROBE_BYTES])
+        .send();
+    let elapsed = start.elapsed();
+    
+    match probe {
+        // Any response means the payload went out, even if the server doesn't handle the path
+        Ok(_) if !elapsed.is_zero() => Some(BANDWIDTH_PROBE_BYTES as f64 / elapsed.as_secs_f64()),
+        _ => None,
+    }
+}
+
+/// Freshly built archives at least this large are hashed through a memory map instead of buffered reads
+const MMAP_HASH_THRESHOLD_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Compute a file's digest with the given algorithm as lowercase hex.
+///
+/// With `allow_mmap`, large files are memory-mapped, falling back to streaming reads when
+/// mapping fails or the file changed while it was being hashed. Only pass it for archives
+/// this process has just written and closed; anything else may be truncated underneath us.
+fn file_digest(path: &Path, algo: HashAlgo, allow_mmap: bool) -> Result<String> {
+    let file = File::open(path)?;
+    let metadata = file.metadata()?;
+    
+    if allow_mmap && metadata.len() >= MMAP_HASH_THRESHOLD_BYTES {
+        if let Some(digest) = mmap_digest(&file, &metadata, algo) {
+            return Ok(digest);
+        }
+    }
+    
+    buffered_digest(file, algo)
+}
+
+/// Hash a file through a memory map, or `None` if it can't be mapped or changed meanwhile
+fn mmap_digest(file: &File, before: &std::fs::Metadata, algo: HashAlgo) -> Option<String> {
+    // SAFETY: the map is only read while hashing. Another process truncating the file in
+    // that window can still fault (SIGBUS on Unix); a size or mtime change seen afterwards
+    // discards the result and the caller rehashes with ordinary reads.
+    let map = unsafe { memmap2::Mmap::map(file) }.ok()?;
+    let digest = bytes_digest(&map, algo);
+    drop(map);
+    
+    let after = file.metadata().ok()?;
+    let unchanged = after.len() == before.len() && after.modified().ok() == before.modified().ok();
+    unchanged.then_some(digest)
+}
+
+/// Digest of an in-memory buffer as lowercase hex
+fn bytes_digest(bytes: &[u8], algo: HashAlgo) -> String {
+    match algo {
+        HashAlgo::Sha256 => format!("{:x}", Sha256::digest(bytes)),
+        HashAlgo::Sha512 => format!("{:x}", Sha512::digest(bytes)),
+        HashAlgo::Blake3 => blake3::hash(bytes).to_hex().to_string(),
+    }
+}
+
+/// Hash a file by streaming its contents through the hasher
+fn buffered_digest(mut file: File, algo: HashAlgo) -> Result<String> {
+    match algo {
+        HashAlgo::Sha256 => {
+            let mut hasher = Sha256::new();
+            copy(&mut file, &mut hasher)?;
+            Ok(format!("{:x}", hasher.finalize()))
+        }
+        HashAlgo::Sha512 => {
+            let mut hasher = Sha512::new();
+            copy(&mut file, &mut hasher)?;
+            Ok(format!("{:x}", hasher.finalize()))
+        }
+        HashAlgo::Blake3 => {
+            let mut hasher = blake3::Hasher::new();
+            copy(&mut file, &mut hasher)?;
+            Ok(hasher.finalize().to_hex().to_string())
+        }
+    }
+}
+
+/// Digest an archive for upload, returning its SHA-256 and the checksum form fields.
+///
+/// The SHA-256 is always sent on its own so servers can audit and dedupe on one digest;
+/// `checksum` uses the requested algorithm, reusing the SHA-256 when that is the choice.
+/// The archive was just built by this run, so it may be hashed through a memory map.
+fn archive_checksum_fields(path: &Path, hash_algo: HashAlgo) -> Result<(String, Vec<(&'static str, String)>)> {
+    let checksum = file_digest(path, hash_algo, true)?;
+    let sha256 = match hash_algo {
+        HashAlgo::Sha256 => checksum.clone(),
+        _ => {
+            status!("🔐 Archive {}: {}", hash_algo.as_str(), checksum);
+            file_digest(path, HashAlgo::Sha256, true)?
+        },
+    };
+    status!("🔐 Archive sha256: {}", sha256);
+    
+    let fields = vec![
+        ("sha256", sha256.clone()),
+        ("checksum", checksum),
+        ("checksum_algo", hash_algo.as_str().to_string()),
+    ];
+    Ok((sha256, fields))
+}
+
+/// Compute the SHA-256 of a file, streaming its contents through the hasher
+fn sha256_file(path: &Path) -> Result<String> {
+    file_digest(path, HashAlgo::Sha256, false)
+}
+
+/// Longest submission ID accepted from the server or `--id`
+const MAX_SUBMISSION_ID_LEN: usize = 128;
+
+/// Check that a submission ID is safe to put in a URL path and a file name
+fn validate_submission_id(id: &str) -> Result<()> {
+    if id.is_empty() || id.len() > MAX_SUBMISSION_ID_LEN {
+        return Err(anyhow::anyhow!("Submission ID must be between 1 and {} characters", MAX_SUBMISSION_ID_LEN));
+    }
+    if !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(anyhow::anyhow!("Invalid submission ID '{}'. Expected only letters, digits, dashes and underscores", id));
+    }
+    Ok(())
+}
+
+/// Download a stored submission archive from the server to `output`.
+///
+/// The archive is written to a temporary file next to `output` and only moved into place once
+/// it is complete and, when `expected_sha256` is given, matches it, so a failed or corrupt
+/// download never leaves a file behind.
+fn fetch_submission(
+    client: &Client,
+    server_url: &str,
+    config: &SubmissionConfig,
+    submission_id: &str,
+    output: &Path,
+    expected_sha256: Option<&str>,
+) -> Result<()> {
+    validate_submission_id(submission_id)?;
+    let download_url = api_url(server_url, config, &format!("/submissions/{}/download", submission_id))?;
+    status!("📥 Downloading submission {} from {}", submission_id, download_url);
+
+    let response = send_with_retry(&config.retry_on, true, "download", || {
+        let request = client.get(&download_url)
+            .timeout(Duration::from_secs(config.upload_timeout_secs));
+        Ok(authorize(request, config)?.send()?)
+    })?;
+
+    if !response.status().is_success() {
+        return Err(anyhow::anyhow!(
+            "Failed to download submission. Status: {}, Body: {}",
+            response.status(),
+            response.text().unwrap_or_default()
+        ));
+    }
+
+    // Show a byte progress bar when the size is known, a spinner otherwise
+    let progress = match response.content_length() {
+        _ if QUIET_OUTPUT.load(Ordering::Relaxed) => ProgressBar::hidden(),
+        Some(len) => {
+            let bar = ProgressBar::new(len);
+            bar.set_style(ProgressStyle::with_template("{bar:40} {bytes}/{total_bytes} ({eta})")?);
+            bar
+        },
+        None => {
+            let spinner = ProgressBar::new_spinner();
+            spinner.set_style(ProgressStyle::with_template("{spinner} {bytes} downloaded")?);
+            spinner
+        },
+    };
+
+    let dir = match output.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut temp = tempfile::NamedTempFile::new_in(dir)
+        .with_context(|| format!("Failed to create a temporary file in {}", dir.display()))?;
+    let mut reader = progress.wrap_read(response);
+    let copied = copy(&mut reader, &mut temp);
+    progress.finish_and_clear();
+    copied.with_context(|| format!("Failed to download submission {}", submission_id))?;
+    temp.as_file().sync_all()?;
+
+    // Verify against the checksum recorded at submission time before anything lands at `output`
+    match expected_sha256 {
+        Some(expected) => {
+            let actual = sha256_file(temp.path())?;
+            if actual != expected {
+                return Err(anyhow::anyhow!(
+                    "Checksum mismatch for submission {}. Expected {}, got {}",
+                    submission_id,
+                    expected,
+                    actual
+                ));
+            }
+            status!("✅ Checksum verified: {}", actual);
+        },
+        None => warn("No checksum recorded for this submission, skipping verification"),
+    }
+
+    temp.persist(output)
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+    message!("✅ Downloaded submission to: {}", output.display());
+
+    Ok(())
+}
+
+#[derive(Deserialize, Debug)]
+struct InitiateResponse {
+    id: String,
+}
+
+/// Ask the server to open a pending submission, returning its ID if the server supports it
+fn initiate_submission(client: &Client, server_url: &str, config: &SubmissionConfig, competition_id: Option<&str>) -> Option<String> {
+    let initiate_url = api_url(server_url, config, "/submissions/initiate").ok()?;
+    
+    let mut request = client.post(&initiate_url);
+    if let Some(comp_id) = competition_id {
+        request = request.query(&[("competition", comp_id)]);
+    }
+    
+    let response = authorize(request, config).ok()?.send().ok()?;
+    if !response.status().is_success() {
+        return None;
+    }
+    
+    response.json::<InitiateResponse>().ok().map(|initiated| initiated.id)
+}
+
+/// Best-effort cancellation of a pending submission after a failed upload
+fn cancel_pending_submission(client: &Client, server_url: &str, config: &SubmissionConfig, pending_id: &str) {
+    let result = api_url(server_url, config, &format!("/submissions/{}", pending_id))
+        .and_then(|cancel_url| authorize(client.delete(cancel_url), config))
+        .and_then(|request| Ok(request.send()?));
+    
+    match result {
+        Ok(response) if response.status().is_success() => {
+            message!("🧹 Cancelled pending submission {}", pending_id);
+        },
+        Ok(response) => {
+            warn(format!("Failed to cancel pending submission {}. Status: {}", pending_id, response.status()));
+        },
+        Err(err) => {
+            warn(format!("Failed to cancel pending submission {}: {}", pending_id, err));
+        },
+    }
+}
+
+/// Check for the latest version available on GitHub
+fn check_for_updates(channel: UpdateChannel) -> Result<Option<(Version, UpdateAsset)>> {
+    let current_version = Version::parse(env!("CARGO_PKG_VERSION"))?;
+    println!("🔄 Checking for updates... Current version: {}", current_version);
+    
+    // Get the repository URL from Cargo.toml metadata
+    let repository = env!("CARGO_PKG_REPOSITORY")
+        .trim_end_matches(".git")
+        .trim_end_matches('/');
+    
+    // Extract owner and repo name from the URL
+    let repo_parts: Vec<&str> = repository.split('/').collect();
+    let (owner, repo) = if repo_parts.len() >= 2 {
+        (repo_parts[repo_parts.len() - 2], repo_parts[repo_parts.len() - 1])
+    } else {
+        return Err(anyhow::anyhow!("Invalid repository URL format in Cargo.toml"));
+    };
+    
+    // `/releases/latest` never returns a prerelease, so that channel lists them all
+    let github_api_url = match channel {
+        UpdateChannel::Stable => format!("https://api.github.com/repos/{}/{}/releases/latest", owner, repo),
+        UpdateChannel::Prerelease => format!("https://api.github.com/repos/{}/{}/releases", owner, repo),
+    };
+    println!("🔍 Checking GitHub API: {}", github_api_url);
+    
+    let client = update_client()?;
+    let response = send_with_retry(&[], true, "GitHub release check", || {
+        Ok(client.get(&github_api_url).send()?)
+    })?;
+    
+    // Handle 404 status specifically (no releases found)
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        println!("❓ No official releases found for this project yet.");
+        return Ok(None);
+    } else if !response.status().is_success() {
+        return Err(anyhow::anyhow!(
+            "Failed to check for updates. Status: {}",
+            response.status()
+        ));
+    }
+    
+    check_clock_skew(&response);
+    let (latest_version, release) = match channel {
+        UpdateChannel::Stable => {
+            let release: GithubRelease = response.json()?;
+            (release_version(&release)?, release)
+        },
+        UpdateChannel::Prerelease => match newest_release(response.json()?) {
+            Some(newest) => newest,
+            None => {
+                println!("❓ No official releases found for this project yet.");
+                return Ok(None);
+            },
+        },
+    };
+    
+    // Find the appropriate asset based on platform and preferred file types
+    let asset = if cfg!(windows) {
+        // For Windows, prefer .exe, .msi, .bat or .ps1 installers
+        release.assets.iter()
+            .find(|asset| asset.name.ends_with(".exe") || asset.name.ends_with(".msi"))
+            .or_else(|| release.assets.iter().find(|asset| asset.name.ends_with(".bat") || asset.name.ends_with(".cmd")))
+            .or_else(|| release.assets.iter().find(|asset| asset.name.ends_with(".ps1")))
+            .or_else(|| release.assets.iter().find(|asset| asset.name.ends_with(".zip")))
+    } else if cfg!(unix) {
+        // For Unix, prefer shell scripts
+        release.assets.iter()
+            .find(|asset| asset.name.contains("direct-install"))
+            .or_else(|| release.assets.iter().find(|asset| asset.name.ends_with(".sh")))
+            .or_else(|| release.assets.iter().find(|asset| asset.name.ends_with(".bash") || asset.name.ends_with(".zsh")))
+            .or_else(|| release.assets.iter().find(|asset| asset.name.ends_with(".tar.gz") || asset.name.ends_with(".tgz")))
+    } else {
+        // For other platforms, just try to find a common installer format
+        release.assets.iter()
+            .find(|asset| asset.name.contains("install") || asset.name.contains("setup"))
+    }
+    .ok_or_else(|| anyhow::anyhow!("No suitable installation file found for your platform in the latest release"))?;
+    
+    // A `<installer>.sha256` asset lets the download be verified before it runs
+    let checksum_name = format!("{}.sha256", asset.name);
+    let checksum_url = release.assets.iter()
+        .find(|candidate| candidate.name == checksum_name)
+        .map(|candidate| candidate.browser_download_url.clone());
+    
+    if latest_version > current_version {
+        println!("📦 New version available: {} (current: {})", latest_version, current_version);
+        Ok(Some((latest_version, UpdateAsset {
+            download_url: asset.browser_download_url.clone(),
+            checksum_url,
+        })))
+    } else {
+        println!("✅ You have the latest version: {}", current_version);
+        Ok(None)
+    }
+}
+
+/// A release's version from its tag, ignoring a leading `v`
+fn release_version(release: &GithubRelease) -> Result<Version> {
+    Ok(Version::parse(release.tag_name.trim_start_matches('v'))?)
+}
+
+/// The published release with the highest version, prereleases included.
+///
+/// Drafts and tags that aren't semver are skipped; semver ordering ranks `1.2.0-rc.1`
+/// below `1.2.0` and above `1.1.0`.
+fn newest_release(releases: Vec<GithubRelease>) -> Option<(Version, GithubRelease)> {
+    releases.into_iter()
+        .filter(|release| !release.draft)
+        .filter_map(|release| release_version(&release).ok().map(|version| (version, release)))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+}
+
+/// Check a file against a published checksum in `sha256sum` format (`<hex>  <name>`, or just the hex)
+fn verify_published_sha256(path: &Path, published: &str) -> Result<()> {
+    let expected = published.split_whitespace()
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("The published checksum is empty"))?
+        .to_ascii_lowercase();
+    let actual = sha256_file(path)?;
+    
+    if actual != expected {
+        return Err(anyhow::anyhow!(
+            "Checksum mismatch for {}: expected {}, got {}. Refusing to install",
+            path.display(),
+            expected,
+            actual
+        ));
+    }
+    Ok(())
+}
+
+/// Download and install the latest version
+fn update_to_latest(asset: &UpdateAsset, force: bool) -> Result<()> {
+    let download_url = asset.download_url.as_str();
+
+    // Create a temporary directory to store the download
+    let temp_dir = tempdir()?;
+
+    // Get filename from URL
+    let url_parts: Vec<&str> = download_url.split('/').collect();
+    let filename = url_parts.last()
+        .ok_or_else(|| anyhow::anyhow!("Invalid download URL"))?;
+
+    let download_path = temp_dir.path().join(filename);
+
+    println!("📥 Downloading latest version from {}...", download_url);
+
+    // Download the installation file
+    let client = update_client()?;
+    let mut response = send_with_retry(&[], true, "Download", || {
+        Ok(client.get(download_url).send()?)
+    })?;
+
+    let mut file = File::create(&download_path)?;
+    copy(&mut response, &mut file)?;
+    drop(file);
+    
+    // Never run an installer that doesn't match its published checksum
+    match &asset.checksum_url {
+        Some(checksum_url) => {
+            let published = send_with_retry(&[], true, "Checksum download", || {
+                Ok(client.get(checksum_url).send()?)
+            })?.error_for_status()?.text()?;
+            verify_published_sha256(&download_path, &published)?;
+            println!("🔐 Checksum verified");
+        }
+        None => println!("⚠️ This release publishes no checksum; the download could not be verified."),
+    }
+
+    // Make shell scripts executable on Unix platforms
+    #[cfg(unix)]
+    if filename.ends_with(".sh") {
+        use std::os::unix::fs::PermissionsExt;
+        let mut perms = std::fs::metadata(&download_path)?.permissions();
+        perms.set_mode(0o755);
+        std::fs::set_permissions(&download_path, perms)?;
+    }
+
+    // Prompt for confirmation unless force flag is set
+    if !force {
+        let confirm = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("Ready to install the latest version. Continue?")
+            .default(true)
+            .interact()?;
+
+        if !confirm {
+            println!("❌ Update cancelled.");
+            return Ok(());
+        }
+    }
+
+    println!("🔄 Installing latest version...");
+
+    // Handle different file types for different platforms
+    #[cfg(unix)]
+    let result = handle_unix_update(&download_path, filename);
+
+    #[cfg(windows)]
+    let result = handle_windows_update(&download_path, filename);
+
+    // Use a generic fallback for other platforms
+    #[cfg(not(any(unix, windows)))]
+    let result = handle_generic_update(&download_path, filename);
+
+    result
+}
+
+#[cfg(unix)]
+fn handle_unix_update(download_path: &Path, filename: &str) -> Result<()> {
+    let status = if filename.ends_with(".sh") {
+        // Run the shell script directly
+        Command::new(download_path).status()?
+    } else if filename.ends_with(".bash") || filename.ends_with(".zsh") {
+        // Run with appropriate shell
+        let shell = if filename.ends_with(".bash") { "bash" } else { "zsh" };
+        Command::new(shell).arg(download_path).status()?
+    } else if filename.ends_with(".zip") || filename.ends_with(".tar.gz") || filename.ends_with(".tgz") {
+        // For archives, ask the user to extract manually
+        println!("📦 Downloaded archive. Manual extraction and installation required.");
+        println!("   Download saved to: {}", download_path.display());
+        return Ok(());
+    } else {
+        // For any other file type, inform the user
+        println!("📄 Downloaded file: {}", download_path.display());
+        println!("   Manual installation required. Check the project documentation.");
+        return Ok(());
+    };
+
+    if status.success() {
+        println!("✅ Successfully updated to the latest version!");
+        println!("   Please restart your terminal or reload your shell for the changes to take effect.");
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(
+            "Failed to install the latest version. Exit code: {}",
+            status.code().unwrap_or(-1)
+        ))
+    }
+}
+
+#[cfg(windows)]
+fn handle_windows_update(download_path: &Path, filename: &str) -> Result<()> {
+    let status = if filename.ends_with(".exe") {
+        // Run the installer executable
+        Command::new(download_path).status()?
+    } else if filename.ends_with(".msi") {
+        // Run the MSI installer
+        Command::new("msiexec").args(["/i", &download_path.to_string_lossy()]).status()?
+    } else if filename.ends_with(".bat") || filename.ends_with(".cmd") {
+        // Run Windows batch file
+        Command::new("cmd").args(["/C", &download_path.to_string_lossy()]).status()?
+    } else if filename.ends_with(".ps1") {
+        // Run PowerShell script
+        Command::new("powershell")
+            .args(["-ExecutionPolicy", "Bypass", "-File", &download_path.to_string_lossy()])
+            .status()?
+    } else if filename.ends_with(".zip") {
+        // For zip archives, give instructions
+        println!("📦 Downloaded archive. Manual extraction and installation required.");
+        println!("   Download saved to: {}", download_path.display());
+        println!("   You can extract this file and run any installation scripts inside.");
+        return Ok(());
+    } else {
+        // For any other file type
+        println!("📄 Downloaded file: {}", download_path.display());
+        println!("   Manual installation required. Check the project documentation.");
+        return Ok(());
+    };
+
+    if status.success() {
+        println!("✅ Successfully updated to the latest version!");
+        println!("   Please restart your command prompt or PowerShell for the changes to take effect.");
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(
+            "Failed to install the latest version. Exit code: {}",
+            status.code().unwrap_or(-1)
+        ))
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn handle_generic_update(download_path: &Path, filename: &str) -> Result<()> {
+    // Generic fallback for any other platform
+    println!("📥 Downloaded update file: {}", download_path.display());
+    println!("⚠️ Automatic installation not supported on this platform.");
+    println!("   Please follow the manual installation instructions from the project documentation.");
+    Ok(())
+}
+
+/// Retries spent on network requests during this run, reported in the summary
+static RETRIES_USED: AtomicU32 = AtomicU32::new(0);
+
+/// Attempts made for each network request by default, including the first
+const DEFAULT_REQUEST_ATTEMPTS: u32 = 3;
+
+/// Attempts made for each network request, set by `--retries`
+static MAX_REQUEST_ATTEMPTS: AtomicU32 = AtomicU32::new(DEFAULT_REQUEST_ATTEMPTS);
+
+/// First wait between attempts, doubled after each retry up to `RETRY_MAX_DELAY`
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Longest `Retry-After` we are willing to sleep for
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+/// Reject `retry_on` entries that aren't HTTP status codes
+fn validate_retry_on(codes: &[u16]) -> Result<()> {
+    if let Some(code) = codes.iter().find(|code| !(100..=599).contains(*code)) {
+        return Err(anyhow::anyhow!("Invalid retry_on status code {}. Expected 100-599", code));
+    }
+    Ok(())
+}
+
+/// Whether a response status should be retried: gateway errors (502, 503, 504) plus the
+/// configured `retry_on` codes. Client errors such as 401 or 403 are never retried by default.
+fn is_retryable_status(retry_on: &[u16], status: reqwest::StatusCode) -> bool {
+    matches!(status.as_u16(), 502..=504) || retry_on.contains(&status.as_u16())
+}
+
+/// Exponential backoff before retry number `retry`, with up to 50% jitter so parallel
+/// clients don't retry in lockstep
+fn backoff_delay(retry: u32) -> Duration {
+    let delay = RETRY_BASE_DELAY
+        .saturating_mul(1 << retry.saturating_sub(1).min(16))
+        .min(RETRY_MAX_DELAY);
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+    delay + delay.mul_f64(f64::from(nanos) / 2e9)
+}
+
+/// Delay requested by a 429 or 503 response's `Retry-After` header (seconds or an HTTP date)
+fn retry_after(response: &Response) -> Option<Duration> {
+    let status = response.status();
+    if status != reqwest::StatusCode::TOO_MANY_REQUESTS && status != reqwest::StatusCode::SERVICE_UNAVAILABLE {
+        return None;
+    }
+    
+    let value = response.headers().get(reqwest::header::RETRY_AFTER)?.to_str().ok()?.trim();
+    let secs = match value.parse::<u64>() {
+        Ok(secs) => secs,
+        Err(_) => parse_http_date(value)?.saturating_sub(unix_now()),
+    };
+    Some(Duration::from_secs(secs).min(MAX_RETRY_AFTER))
+}
+
+/// Send a request, retrying connection errors and retryable statuses with backoff.
+///
+/// `send` is called once per attempt so the request can be rebuilt each time. The last
+/// response is returned as-is once attempts run out, leaving error reporting to the caller.
+/// Timeouts are only retried for `idempotent` requests: a submission that timed out may
+/// still have been accepted, and sending it again could count it twice.
+fn send_with_retry(
+    retry_on: &[u16],
+    idempotent: bool,
+    what: &str,
+    mut send: impl FnMut() -> Result<Response>,
+) -> Result<Response> {
+    let max_attempts = MAX_REQUEST_ATTEMPTS.load(Ordering::Relaxed);
+    let mut attempt = 1;
+    loop {
+        let started = Instant::now();
+        let result = send();
+        match &result {
+            Ok(response) => detail!("{} attempt {}: HTTP {} in {:.2?}", what, attempt, response.status(), started.elapsed()),
+            Err(err) => detail!("{} attempt {}: {} after {:.2?}", what, attempt, err, started.elapsed()),
+        }
+        
+        let delay = match result {
+            Ok(response) if attempt < max_attempts && is_retryable_status(retry_on, response.status()) => {
+                status!("⚠️ {} returned {}; retrying ({}/{})", what, response.status(), attempt, max_attempts - 1);
+                retry_after(&response).unwrap_or_else(|| backoff_delay(attempt))
+            }
+            Err(err) if attempt < max_attempts && is_transient(&err, idempotent) => {
+                status!("⚠️ {} failed: {}; retrying ({}/{})", what, err, attempt, max_attempts - 1);
+                backoff_delay(attempt)
+            }
+            result => return result,
+        };
+        
+        RETRIES_USED.fetch_add(1, Ordering::SeqCst);
+        thread::sleep(delay);
+        attempt += 1;
+    }
+}
+
+/// Whether an error is worth retrying: a refused or failed connection, which never reached
+/// the server, or a timeout when the request is safe to repeat
+fn is_transient(err: &anyhow::Error, idempotent: bool) -> bool {
+    err.downcast_ref::<reqwest::Error>()
+        .map(|err| err.is_connect() || (idempotent && err.is_timeout()))
+        .unwrap_or(false)
+}
+
+/// Exit code used when `--min-interval` skips a submission
+const EXIT_THROTTLED: i32 = 3;
+
+/// How an `optimus send` run ended
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+enum RunStatus {
+    Submitted,
+    Cancelled,
+    NotApproved,
+    ManifestOnly,
+    DryRun,
+    Saved,
+    Throttled,
+    Failed,
+}
+
+impl RunStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RunStatus::Submitted => "submitted",
+            RunStatus::Cancelled => "cancelled",
+            RunStatus::NotApproved => "not-approved",
+            RunStatus::ManifestOnly => "manifest-only",
+            RunStatus::DryRun => "dry-run",
+            RunStatus::Saved => "saved",
+            RunStatus::Throttled => "throttled",
+            RunStatus::Failed => "failed",
+        }
+    }
+}
+
+/// End-of-run statistics for `optimus send`
+#[derive(Serialize, Debug)]
+struct RunSummary {
+    status: RunStatus,
+    elapsed_secs: f64,
+    bytes_uploaded: u64,
+    uncompressed_bytes: u64,
+    compression_ratio: Option<f64>,
+    retries: u32,
+    server_status: Option<u16>,
+    remaining_attempts: Option<i32>,
+    submission_id: Option<String>,
+    estimated_upload_secs: Option<f64>,
+    note: Option<String>,
+    error: Option<String>,
+}
+
+impl Default for RunSummary {
+    fn default() -> Self {
+        RunSummary {
+            status: RunStatus::Cancelled,
+            elapsed_secs: 0.0,
+            bytes_uploaded: 0,
+            uncompressed_bytes: 0,
+            compression_ratio: None,
+            retries: 0,
+            server_status: None,
+            remaining_attempts: None,
+            submission_id: None,
+            estimated_upload_secs: None,
+            note: None,
+            error: None,
+        }
+    }
+}
+
+/// Print the end-of-run summary as a single block
+fn print_summary(summary: &RunSummary) {
+    println!();
+    println!("📋 Summary");
+    println!("   Status:      {}", summary.status.as_str());
+    println!("   Time:        {:.1}s", summary.elapsed_secs);
+    
+    if summary.uncompressed_bytes > 0 {
+        println!("   Content:     {}", format_bytes(summary.uncompressed_bytes));
+    }
+    
+    if summary.bytes_uploaded > 0 {
+        println!("   Uploaded:    {}", format_bytes(summary.bytes_uploaded));
+    }
+    
+    if let Some(estimate) = summary.estimated_upload_secs {
+        println!("   Estimate:    ~{:.0}s upload (approximate)", estimate.max(1.0));
+    }
+    
+    if let Some(ratio) = summary.compression_ratio {
+        println!("   Compression: {:.1}% of original size", ratio * 100.0);
+    }
+    
+    println!("   Retries:     {}", summary.retries);
+    
+    if let Some(status) = summary.server_status {
+        println!("   Server:      HTTP {}", status);
+    }
+    
+    if let Some(id) = &summary.submission_id {
+        println!("   Submission:  {}", id);
+    }
+    
+    if let Some(note) = &summary.note {
+        println!("   Note:        {}", note);
+    }
+    
+    if let Some(error) = &summary.error {
+        println!("   Error:       {}", error);
+    }
+}
+
+/// The single line printed by `--quiet-success`
+fn print_quiet_result(summary: &RunSummary) {
+    match summary.server_status {
+        Some(status) => println!(
+            "{}: {} in {:.1}s (HTTP {})",
+            summary.status.as_str(),
+            format_bytes(summary.bytes_uploaded),
+            summary.elapsed_secs,
+            status
+        ),
+        None => println!("{}", summary.status.as_str()),
+    }
+}
+
+/// Run `optimus send`, recording what happened in `summary`
+fn run_send(args: &SendArgs, summary: &mut RunSummary) -> Result<()> {
+    let SendArgs {
+        config,
+        config_optional,
+        competition_id,
+        competition_file,
+        api_key,
+        server,
+        proxy,
+        insecure,
+        check_timeout,
+        upload_timeout,
+        retries,
+        retry_on,
+        compression,
+        compression_method,
+        archive_format,
+        max_size,
+        force_format,
+        auto_confirm,
+        read_threads,
+        upload_concurrency,
+        print_manifest_only,
+        dry_run,
+        output,
+        strip_prefix,
+        add_prefix,
+        environment,
+        include_config,
+        fail_on_warnings,
+        delta,
+        allow_localhost,
+        optimize_for,
+        from_archive_dir,
+        max_depth,
+        min_interval,
+        exclude,
+        include,
+        exclude_empty_dirs,
+        gitignore,
+        modified_within,
+        temp_dir,
+        review,
+        parallel_hash,
+        hash_algo,
+        no_server_excludes,
+        content_type,
+        label,
+        overwrite_label,
+        note,
+        require_clean_git,
+        allow_dirty,
+        strict_secrets,
+        no_preflight,
+        no_cleanup,
+        manifest_against,
+        warn_only,
+        prepared,
+        quiet_success: _,
+        silent: _,
+        summary: _,
+        no_summary: _,
+    } = args;
+    
+    // Reject a bad note, label or window before doing any work
+    if let Some(note) = note {
+        validate_note(note)?;
+    }
+    if let Some(label) = label {
+        validate_label(label)?;
+    }
+    if let Some(content_type) = content_type {
+        validate_content_type(content_type)?;
+    }
+    if *review && !std::io::stdin().is_terminal() {
+        return Err(anyhow::anyhow!("--review needs an interactive terminal"));
+    }
+    if JSON_OUTPUT.load(Ordering::Relaxed) && (*dry_run || *print_manifest_only || *review) {
+        return Err(anyhow::anyhow!("--json can't be combined with --dry-run, --print-manifest-only or --review"));
+    }
+    let show_progress = !QUIET_OUTPUT.load(Ordering::Relaxed);
+    let modified_since = modified_within.as_deref()
+        .map(parse_duration)
+        .transpose()?
+        .map(|window| SystemTime::now().checked_sub(window).unwrap_or(UNIX_EPOCH));
+    
+    // Load the configuration and overlay the selected environment
+    let mut config_data = load_layered_config(config, config_optional)?;
+    apply_environment(&mut config_data, environment.as_deref())?;
+    
+    // Override config with command line arguments if provided
+    if let Some(api) = api_key {
+        config_data.api_key = api.clone();
+    }
+    if let Some(url) = proxy {
+        config_data.proxy = Some(url.clone());
+    }
+    if *insecure {
+        config_data.allow_insecure_tls = true;
+    }
+    if let Some(secs) = check_timeout {
+        config_data.check_timeout_secs = *secs;
+    }
+    if let Some(secs) = upload_timeout {
+        config_data.upload_timeout_secs = *secs;
+    }
+    if let Some(size) = max_size {
+        config_data.max_archive_bytes = Some(parse_size(size)?);
+    }
+    config_data.exclude.extend(exclude.iter().cloned());
+    config_data.include.extend(include.iter().cloned());
+    validate_retry_on(retry_on)?;
+    config_data.retry_on.extend(retry_on);
+    MAX_REQUEST_ATTEMPTS.store(*retries, Ordering::Relaxed);
+    // Nothing is sent to the server when only listing or describing the archive
+    let local_only = *print_manifest_only || *dry_run;
+    if !local_only {
+        ensure_api_key(&config_data)?;
+    }
+    
+    let competition_from_file = match competition_file {
+        Some(path) if competition_id.is_none() => Some(read_competition_file(path)?),
+        _ => None,
+    };
+    let archive_format = archive_format.unwrap_or(config_data.archive_format);
+    if *delta && archive_format != ArchiveFormat::Zip {
+        return Err(anyhow::anyhow!("--delta only supports zip archives"));
+    }
+    
+    let mut competition = competition_id.as_deref()
+        .or(competition_from_file.as_deref())
+        .or(config_data.competition_id.as_deref())
+        .map(|id| resolve_competition_alias(&config_data, id));
+    
+    let server_url = match server {
+        Some(s) => s.clone(),
+        None => config_data.server_url.clone(),
+    };
+    // Catch a bad submit_path before the archive is built rather than at upload time
+    let submit_url = api_url(&server_url, &config_data, &config_data.submit_path)?;
+    
+    // --optimize-for overrides the explicit level and picks the method too
+    let compression_settings = match optimize_for {
+        Some(goal) => {
+            let settings = compression_for_goal(*goal);
+            status!(
+                "⚙️ Optimizing for {:?}: {:?} at level {}",
+                goal,
+                settings.method,
+                settings.level
+            );
+            settings
+        },
+        None => CompressionSettings {
+            method: configured_compression_method(compression_method.as_deref(), &config_data)?,
+            level: compression.unwrap_or(config_data.compression_level),
+        },
+    };
+    
+    let layout = EntryLayout {
+        strip_prefix: strip_prefix.clone(),
+        add_prefix: add_prefix.clone(),
+    };
+    
+    let auto_confirm_submission = *auto_confirm || config_data.preferences.auto_confirm;
+    
+    // A piped config leaves no stdin to answer prompts with
+    if config.iter().any(|path| path == STDIN_CONFIG) && !auto_confirm_submission && !local_only {
+        return Err(anyhow::anyhow!("--config - reads the config from stdin, so add --auto-confirm to skip prompts"));
+    }
+    
+    // Throttle over-eager schedulers using the local history
+    if let Some(interval) = min_interval.as_ref().or(config_data.min_interval.as_ref()) {
+        let interval = parse_duration(interval)?;
+        let now = unix_now();
+        if let Some(remaining) = remaining_interval(&read_history()?, competition.as_deref(), interval, now) {
+            status!(
+                "⏳ Last successful submission was less than {} ago. Skipping; next allowed in {}.",
+                format_duration(interval),
+                format_duration(remaining)
+            );
+            summary.status = RunStatus::Throttled;
+            return Ok(());
+        }
+    }
+    
+    // A manifest or saved archive never leaves the machine, so only real submissions are guarded
+    if !local_only && output.is_none() {
+        let explicit = server.is_some() || config_data.server_url_set;
+        if !guard_default_server(&server_url, explicit, *allow_localhost, auto_confirm_submission)? {
+            message!("❌ Submission cancelled.");
+            return Ok(());
+        }
+    }
+    
+    // One client for the whole run so the check and submit share connections
+    let client = build_client(&config_data)?;
+    
+    // Fail fast if the server is down rather than after building a large archive
+    if !*no_preflight && !local_only {
+        check_server_health(&client, &server_url, &config_data)?;
+    }
+    
+    // Upload an archive staged earlier by `optimus prepare`
+    if let Some(dir) = prepared {
+        let mut fields = Vec::new();
+        if let Some(note) = note {
+            fields.push(("note", note.clone()));
+        }
+        if let Some(label) = label {
+            fields.push(("label", label.clone()));
+            if *overwrite_label {
+                fields.push(("overwrite_label", "true".to_string()));
+            }
+        }
+        summary.note = note.clone();
+        let upload = UploadOptions {
+            competition_id: competition.as_deref(),
+            fields: &fields,
+            content_type: None,
+            parallel_hash: None,
+            keep_archive: true,
+            show_progress,
+            chunked: false,
+            concurrency: usize::from(*upload_concurrency),
+        };
+        return upload_prepared(&client, &server_url, &config_data, Path::new(dir), &upload, summary);
+    }
+    
+    // Offer the user's competitions when none was given and someone can pick one
+    if competition.is_none() && !auto_confirm_submission && !local_only && std::io::stdin().is_terminal() {
+        competition = select_competition(&client, &server_url, &config_data)?;
+    }
+    let comp_id = competition.as_deref();
+    
+    // Set when the server check offers a base for delta submissions
+    let mut base_manifest_url = None;
+    // Set when the server check lists forbidden paths
+    let mut server_excludes = None;
+    // Set when the server check advertises chunked uploads
+    let mut supports_chunked = false;
+    
+    // Determine the format to use - either from force_format, config, or server check
+    let format = if let Some(forced) = force_format {
+        status!("⚠️ Bypassing server check, using forced format: {}", forced);
+        forced.clone()
+    } else if let Some(config_format) = &config_data.format {
+        status!("⚠️ Using format from config file: {}", config_format);
+        config_format.clone()
+    } else if *dry_run {
+        status!("ℹ️ Dry run without a configured format; listing the 'repo' format");
+        "repo".to_string()
+    } else {
+        // Contact the server to check for submission approval and format
+        let (check_response, cooldown) = check_with_server(&client, &server_url, &config_data, comp_id)?;
+        summary.remaining_attempts = Some(check_response.remaining_attempts);
+
+        // Uploading during the server's cooldown would only be rejected
+        if let Some(remaining) = cooldown {
+            if !*print_manifest_only && output.is_none() {
+                status!("⏳ The server is still cooling down. Skipping; try again in {}.", format_duration(remaining));
+                summary.status = RunStatus::Throttled;
+                return Ok(());
+            }
+        }
+
+        // Check if submission is approved (a manifest or saved archive doesn't spend an attempt)
+        if !check_response.submission_approved && !*print_manifest_only && output.is_none() {
+            message!("❌ Submission not allowed. No remaining attempts.");
+            summary.status = RunStatus::NotApproved;
+            return Ok(());
+        }
+
+        // Prompt the user for confirmation
+        if !auto_confirm_submission && !*print_manifest_only && output.is_none() {
+            let confirm_msg = format!(
+                "Proceed with submission? You have {} attempts remaining.",
+                check_response.remaining_attempts
+            );
+
+            let confirmed = Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(confirm_msg)
+                .default(true)
+                .interact()?;
+
+            if !confirmed {
+                message!("❌ Submission cancelled.");
+                return Ok(());
+            }
+        }
+
+        base_manifest_url = check_response.base_manifest_url;
+        supports_chunked = check_response.supports_chunked.unwrap_or(false);
+        server_excludes = check_response.server_excludes;
+        check_response.required_format
+    };
+    
+    validate_format(&format)?;
+    
+    // Apply the organizers' excludes, falling back to the last ones seen when the check was skipped
+    if !*no_server_excludes {
+        let excludes = match server_excludes {
+            Some(excludes) => {
+                save_server_excludes(comp_id, &excludes);
+                Some(excludes)
+            }
+            None => load_server_excludes(comp_id).map(|cached| {
+                warn(format!(
+                    "Using server excludes cached {} ago; they may be out of date",
+                    format_duration(Duration::from_secs(unix_now().saturating_sub(cached.fetched_at)))
+                ));
+                cached.excludes
+            }),
+        };
+        config_data.exclude.extend(excludes.unwrap_or_default());
+    }
+    
+    // Archive a pre-staged directory if given; config is still read from here
+    let root = match from_archive_dir {
+        Some(dir) => {
+            let dir = std::fs::canonicalize(dir)
+                .with_context(|| format!("Archive directory '{}' not found", dir))?;
+            if !dir.is_dir() {
+                return Err(anyhow::anyhow!("'{}' is not a directory", dir.display()));
+            }
+            status!("📂 Archiving staged directory: {}", dir.display());
+            dir
+        },
+        None => env::current_dir()?,
+    };
+    
+    // Only committed work may be submitted when a clean tree is required
+    if (*require_clean_git || config_data.require_clean_git) && !*allow_dirty && !local_only {
+        check_clean_git(&root, config_data.require_git_repo)?;
+    }
+    
+    // The active config file is left out unless explicitly requested
+    let collect = CollectOptions {
+        root,
+        format: format.clone(),
+        exclude: config_data.exclude.clone(),
+        include: config_data.include.clone(),
+        skip_paths: if *include_config {
+            Vec::new()
+        } else {
+            config.iter()
+                .chain(config_optional.iter())
+                .filter_map(|path| std::fs::canonicalize(path).ok())
+                .collect()
+        },
+        max_depth: *max_depth,
+        exclude_empty_dirs: *exclude_empty_dirs || !config_data.include_empty_dirs,
+        modified_since,
+        respect_gitignore: *gitignore || config_data.preferences.respect_gitignore,
+        follow_symlinks: config_data.follow_symlinks,
+    };
+    
+    // Check the file set against the competition's expected manifest
+    if let Some(path) = manifest_against {
+        let expected = read_expected_manifest(path)?;
+        let entries = apply_layout(collect_entries(&collect)?, &layout)?;
+        let mismatch = check_manifest_compliance(&expected, &entries);
+        
+        if mismatch.is_empty() {
+            status!("✅ Files match the expected manifest {}", path);
+        } else {
+            for missing in &mismatch.missing {
+                message!("❌ Missing required file: {}", missing);
+            }
+            for extra in &mismatch.extra {
+                message!("❌ Unexpected file: {}", extra);
+            }
+            let message = format!(
+                "Files don't match {}: {} missing, {} unexpected",
+                path, mismatch.missing.len(), mismatch.extra.len()
+            );
+            if !*warn_only {
+                return Err(anyhow::anyhow!(message));
+            }
+            status!("⚠️ {}", message);
+        }
+    }
+    
+    // List what would be archived without writing or uploading anything
+    if *dry_run {
+        print_dry_run(&collect, &layout)?;
+        summary.status = RunStatus::DryRun;
+        return Ok(());
+    }
+    
+    // Emit the manifest for review without compressing or uploading
+    if *print_manifest_only {
+        let entries = collect_entries(&collect)?;
+        let entries = apply_layout(entries, &layout)?;
+        let manifest = build_manifest(&format, &entries)?;
+        println!("{}", serde_json::to_string_pretty(&manifest)?);
+        summary.status = RunStatus::ManifestOnly;
+        return Ok(());
+    }
+    
+    // For delta submissions, diff against the server's base; otherwise send everything
+    let base_manifest = match (*delta, &base_manifest_url) {
+        (true, Some(url)) => Some(fetch_base_manifest(&client, &server_url, url, &config_data)?),
+        (true, None) => {
+            status!("ℹ️ Server offered no base manifest, sending a full submission");
+            None
+        },
+        (false, _) => None,
+    };
+    
+    // Create the archive based on the required format
+    let temp_dir = archive_temp_dir(temp_dir.as_deref())?;
+    detail!("Archiving {} in '{}' format for {}", collect.root.display(), collect.format, server_url);
+    let build_start = Instant::now();
+    let archive = match &base_manifest {
+        Some(base) => create_delta_archive(
+            compression_settings,
+            &collect,
+            usize::from(*read_threads),
+            &layout,
+            base,
+            &temp_dir,
+            show_progress,
+        )?,
+        None if archive_format == ArchiveFormat::Targz => create_targz_archive(
+            compression_settings,
+            &collect,
+            &layout,
+            &temp_dir,
+            show_progress,
+        )?,
+        None => create_zip_archive(
+            compression_settings,
+            &collect,
+            usize::from(*read_threads),
+            &layout,
+            &temp_dir,
+            show_progress,
+        )?,
+    };
+    let mut zip_path = archive.path;
+    detail!("Built archive in {:.2?}", build_start.elapsed());
+    
+    // Rename the archive if the config asks for a specific file name
+    if let Some(template) = &config_data.archive_name {
+        // Delta packages are always zip files
+        let format = if base_manifest.is_some() { ArchiveFormat::Zip } else { archive_format };
+        zip_path = apply_archive_name(zip_path, template, &collect.root, comp_id, format)?;
+    }
+    status!("✅ Created archive at: {} ({} files)", zip_path.display(), archive.files.len());
+    scan_for_secrets(&archive.files, &config_data.secret_patterns, *strict_secrets)?;
+    
+    summary.uncompressed_bytes = archive.files.iter().map(|f| f.size).sum();
+    let archive_size = std::fs::metadata(&zip_path)?.len();
+    print_archive_summary(&archive.files, archive.dir_count, archive_size);
+    if summary.uncompressed_bytes > 0 {
+        summary.compression_ratio = Some(archive_size as f64 / summary.uncompressed_bytes as f64);
+    }
+    
+    if *fail_on_warnings == Some(WarningPolicy::BeforeUpload) {
+        check_warnings()?;
+    }
+    
+    // Keep the archive locally instead of uploading it
+    if let Some(output) = output {
+        let destination = save_archive(&zip_path, Path::new(output))?;
+        status!("💾 Saved archive to {} ({})", destination.display(), format_bytes(archive_size));
+        summary.status = RunStatus::Saved;
+        return Ok(());
+    }
+    
+    check_archive_size(archive_size, config_data.max_archive_bytes)?;
+    
+    // The full file list, kept after upload as the baseline for the next --review.
+    // A delta archive only lists changed files, so it is rebuilt from disk when needed.
+    let current_files = if base_manifest.is_none() {
+        Some(archive.files.clone())
+    } else if *review {
+        Some(build_manifest(&format, &apply_layout(collect_entries(&collect)?, &layout)?)?.files)
+    } else {
+        None
+    };
+    
+    if *review {
+        let current = current_files.as_deref().unwrap_or_default();
+        if !review_changes(comp_id, current, &zip_path)? {
+            message!("❌ Submission cancelled.");
+            return Ok(());
+        }
+    }
+    
+    // Show a rough upload time and give the user a chance to wait for a better connection
+    let interactive = !auto_confirm_submission && std::io::stdin().is_terminal();
+    if config_data.preferences.estimate_upload_time && interactive {
+        if let Some(rate) = upload_rate_estimate(&client, &server_url, &config_data) {
+            let estimate = archive_size as f64 / rate;
+            summary.estimated_upload_secs = Some(estimate);
+            
+            let proceed = Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!(
+                    "Upload {} now? Estimated time ~{} (approximate)",
+                    format_bytes(archive_size),
+                    format_duration(Duration::from_secs_f64(estimate.max(1.0)))
+                ))
+                .default(true)
+                .interact()?;
+            
+            if !proceed {
+                message!("❌ Submission cancelled.");
+                return Ok(());
+            }
+        }
+    }
+    
+    // Ask for a note when none was given and someone is there to answer
+    let note = match note {
+        Some(note) => Some(note.clone()),
+        None if !auto_confirm_submission && std::io::stdin().is_terminal() => prompt_note()?,
+        None => None,
+    };
+    summary.note = note.clone();
+    
+    let mut fields = Vec::new();
+    if base_manifest.is_some() {
+        fields.push(("delta", "true".to_string()));
+    }
+    if let Some(note) = &note {
+        fields.push(("note", note.clone()));
+    }
+    
+    // Let the server check the archive arrived intact. A parallel hash is computed while
+    // uploading and sent after the file instead.
+    let hash_algo = hash_algo.unwrap_or(config_data.hash_algo);
+    let mut archive_sha256 = None;
+    if !*parallel_hash {
+        let (sha256, checksum_fields) = archive_checksum_fields(&zip_path, hash_algo)?;
+        fields.extend(checksum_fields);
+        archive_sha256 = Some(sha256);
+    }
+    
+    if let Some(label) = label {
+        fields.push(("label", label.clone()));
+        if *overwrite_label {
+            fields.push(("overwrite_label", "true".to_string()));
+        }
+    }
+    
+    // Open a pending submission the server can discard if the upload fails
+    let pending_id = if config_data.cancel_on_failure {
+        initiate_submission(&client, &server_url, &config_data, comp_id)
+    } else {
+        None
+    };
+    if let Some(id) = &pending_id {
+        fields.push(("pending_id", id.clone()));
+    }
+    
+    // The upload removes the archive, so keep a copy for diffs in the next review
+    if *review {
+        stash_pending_archive(comp_id, &zip_path);
+    }
+    
+    // Send the zip file to the submit endpoint
+    let keep_archive = *no_cleanup || config_data.preferences.keep_archive;
+    let upload_start = Instant::now();
+    let upload = UploadOptions {
+        competition_id: comp_id,
+        fields: &fields,
+        content_type: content_type.as_deref(),
+        parallel_hash: parallel_hash.then_some(hash_algo),
+        keep_archive,
+        show_progress,
+        chunked: supports_chunked,
+        concurrency: usize::from(*upload_concurrency),
+    };
+    let result = match send_zip_to_endpoint(&client, &zip_path, &config_data, &submit_url, &upload) {
+        Ok(result) => result,
+        Err(err) => {
+            if let Some(id) = &pending_id {
+                cancel_pending_submission(&client, &server_url, &config_data, id);
+            }
+            return Err(err);
+        }
+    };
+    summary.server_status = Some(result.status);
+    summary.submission_id = result.submission_id.clone();
+    summary.bytes_uploaded = archive_size;
+    // History keeps a SHA-256 so `optimus fetch` can verify the submission later; a parallel
+    // hash produced it during the upload rather than in a separate pass
+    if archive_sha256.is_none() {
+        archive_sha256 = result.archive_sha256.clone();
+    }
+    if keep_archive {
+        status!("📦 Kept the uploaded archive at: {}", zip_path.display());
+    }
+    record_history(&config_data.preferences, HistoryEntry {
+        timestamp: unix_now(),
+        competition_id: comp_id.map(str::to_string),
+        format: Some(format.clone()),
+        archive_size: Some(archive_size),
+        submission_id: result.submission_id,
+        server_status: Some(result.status),
+        sha256: archive_sha256,
+        note,
+        label: label.clone(),
+    });
+    if let Some(files) = &current_files {
+        save_last_submission(comp_id, files, *review);
+    }
+    record_upload_rate(archive_size, upload_start.elapsed());
+    summary.status = RunStatus::Submitted;
+    
+    if fail_on_warnings.is_some() {
+        check_warnings()?;
+    }
+    
+    Ok(())
+}
+
+/// Metadata file written next to a prepared archive
+const PREPARED_METADATA_FILE: &str = ".optimus-submission.json";
+
+/// What `optimus prepare` staged, read back by `optimus send --prepared`
+#[derive(Deserialize, Serialize, Debug)]
+struct PreparedSubmission {
+    competition_id: Option<String>,
+    format: String,
+    /// Archive file name inside the prepared directory
+    archive: String,
+    sha256: String,
+    /// When the archive was built, in seconds since the Unix epoch
+    prepared_at: u64,
+    manifest: Vec<ManifestEntry>,
+}
+
+/// Build, validate and checksum a submission of the current directory into `--output`
+fn run_prepare(args: &PrepareArgs) -> Result<()> {
+    let mut config_data = load_layered_config(std::slice::from_ref(&args.config), &[])?;
+    let competition = args.competition_id.as_deref()
+        .or(config_data.competition_id.as_deref())
+        .map(|id| resolve_competition_alias(&config_data, id));
+    
+    // The format comes from the flag or config, falling back to the server when checking
+    let mut format = args.force_format.clone().or_else(|| config_data.format.clone());
+    if args.check {
+        let server_url = args.server.clone().unwrap_or_else(|| config_data.server_url.clone());
+        let client = build_client(&config_data)?;
+        let (check_response, _) = check_with_server(&client, &server_url, &config_data, competition.as_deref())?;
+        if !check_response.submission_approved {
+            return Err(anyhow::anyhow!("The server did not approve a submission"));
+        }
+        format.get_or_insert(check_response.required_format);
+    }
+    let format = format.ok_or_else(|| anyhow::anyhow!(
+        "No submission format known. Pass --force-format, set `format` in the config, or use --check"
+    ))?;
+    validate_format(&format)?;
+    
+    let output = Path::new(&args.output);
+    std::fs::create_dir_all(output)
+        .with_context(|| format!("Failed to create output directory {}", output.display()))?;
+    
+    // Keep earlier metadata out of the archive if the output directory is inside the project
+    let root = env::current_dir()?;
+    config_data.exclude.push(PREPARED_METADATA_FILE.to_string());
+    
+    let zip_path = temp_archive_path(output, &root, "", "zip")?;
+    status!("📦 Creating {} archive at {}", format, zip_path.display());
+    let file = File::create(&zip_path)
+        .with_context(|| format!("Failed to create {}", zip_path.display()))?;
+    let stats = ArchiveBuilder::new(&root)
+        .format(&format)
+        .exclude(config_data.exclude.clone())
+        .include(config_data.include.clone())
+        .skip_paths(std::fs::canonicalize(&args.config).into_iter().collect())
+        .exclude_empty_dirs(!config_data.include_empty_dirs)
+        .respect_gitignore(config_data.preferences.respect_gitignore)
+        .follow_symlinks(config_data.follow_symlinks)
+        .compression(CompressionSettings {
+            method: configured_compression_method(None, &config_data)?,
+            level: args.compression.unwrap_or(config_data.compression_level),
+        })
+        .show_progress(!QUIET_OUTPUT.load(Ordering::Relaxed))
+        .build_to(file)?;
+    
+    let problems = verify_archive(&zip_path, &format, false)?;
+    if !problems.is_empty() {
+        for problem in &problems {
+            println!("❌ {}", problem);
+        }
+        return Err(anyhow::anyhow!("Prepared archive failed verification with {} problem(s)", problems.len()));
+    }
+    
+    let sha256 = sha256_file(&zip_path)?;
+    let prepared = PreparedSubmission {
+        competition_id: competition,
+        format,
+        archive: root_dir_name(&root)? + ".zip",
+        sha256,
+        prepared_at: unix_now(),
+        manifest: stats.files,
+    };
+    let metadata_path = output.join(PREPARED_METADATA_FILE);
+    std::fs::write(&metadata_path, serde_json::to_string_pretty(&prepared)?)
+        .with_context(|| format!("Failed to write {}", metadata_path.display()))?;
+    
+    status!("✅ Prepared {} files ({}) in {}", stats.file_count, format_bytes(stats.archive_bytes), output.display());
+    status!("🔐 Archive sha256: {}", prepared.sha256);
+    status!("   Upload it with: optimus send --prepared {}", output.display());
+    Ok(())
+}
+
+/// Upload an archive staged by `optimus prepare`, refusing it if it changed since then
+///
+/// Without a competition in `upload`, the one recorded when preparing is used.
+fn upload_prepared(
+    client: &Client,
+    server_url: &str,
+    config: &SubmissionConfig,
+    dir: &Path,
+    upload: &UploadOptions,
+    summary: &mut RunSummary,
+) -> Result<()> {
+    let metadata_path = dir.join(PREPARED_METADATA_FILE);
+    let contents = std::fs::read_to_string(&metadata_path)
+        .with_context(|| format!("No prepared submission in {} (missing {})", dir.display(), PREPARED_METADATA_FILE))?;
+    let prepared: PreparedSubmission = serde_json::from_str(&contents)
+        .with_context(|| format!("Failed to parse {}", metadata_path.display()))?;
+    
+    let zip_path = dir.join(&prepared.archive);
+    let sha256 = sha256_file(&zip_path)?;
+    if sha256 != prepared.sha256 {
+        return Err(anyhow::anyhow!(
+            "{} changed since it was prepared (expected sha256 {}, found {})",
+            zip_path.display(),
+            prepared.sha256,
+            sha256
+        ));
+    }
+    status!("✅ Prepared archive checksum verified: {}", sha256);
+    
+    let mut fields = upload.fields.to_vec();
+    fields.push(("sha256", sha256.clone()));
+    fields.push(("checksum", sha256));
+    fields.push(("checksum_algo", HashAlgo::Sha256.as_str().to_string()));
+    
+    let archive_size = std::fs::metadata(&zip_path)?.len();
+    check_archive_size(archive_size, config.max_archive_bytes)?;
+    let upload = UploadOptions {
+        competition_id: upload.competition_id.or(prepared.competition_id.as_deref()),
+        fields: &fields,
+        ..*upload
+    };
+    let upload_start = Instant::now();
+    let submit_url = api_url(server_url, config, &config.submit_path)?;
+    let result = send_zip_to_endpoint(client, &zip_path, config, &submit_url, &upload)?;
+    
+    summary.server_status = Some(result.status);
+    summary.submission_id = result.submission_id.clone();
+    summary.bytes_uploaded = archive_size;
+    let field = |name: &str| fields.iter().find(|(field, _)| *field == name).map(|(_, value)| value.clone());
+    record_history(&config.preferences, HistoryEntry {
+        timestamp: unix_now(),
+        competition_id: upload.competition_id.map(str::to_string),
+        format: Some(prepared.format.clone()),
+        archive_size: Some(archive_size),
+        submission_id: result.submission_id,
+        server_status: Some(result.status),
+        sha256: Some(prepared.sha256.clone()),
+        note: field("note"),
+        label: field("label"),
+    });
+    summary.uncompressed_bytes = prepared.manifest.iter().map(|file| file.size).sum();
+    record_upload_rate(archive_size, upload_start.elapsed());
+    summary.status = RunStatus::Submitted;
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        // `--version --verbose` (or `--version --json`) prints the same build details as `optimus version`
+        Err(err) if err.kind() == ErrorKind::DisplayVersion => {
+            let args: Vec<String> = env::args().skip(1).collect();
+            let json = args.iter().any(|arg| arg == "--json");
+            let verbose = args.iter().any(|arg| {
+                arg == "--verbose" || (arg.starts_with('-') && !arg.starts_with("--") && arg.contains('v'))
+            });
+            if json {
+                print_json("version", &version_info())?;
+            } else if verbose {
+                print_version_info(&version_info());
+            } else {
+                err.exit();
+            }
+            return Ok(());
+        },
+        Err(err) => err.exit(),
+    };
+    QUIET_OUTPUT.store(cli.quiet > 0, Ordering::Relaxed);
+    VERBOSE_OUTPUT.store(cli.verbose > 0, Ordering::Relaxed);
+    JSON_OUTPUT.store(cli.json, Ordering::Relaxed);
+    if let Ok(mut format) = CONFIG_FORMAT.lock() {
+        *format = cli.config_format;
+    }
+    
+    if let Some(path) = &cli.log_file {
+        open_log(path)?;
+    }
+    
+    // Finalize the log on every path so partial diagnostics survive a failed run
+    let result = run_command(&cli);
+    close_log();
+    
+    if cli.json {
+        if let Err(err) = &result {
+            let output = JsonOutput::<()> {
+                command: cli.command.name(),
+                status: "error",
+                result: None,
+                message: Some(format!("{:#}", err)),
+            };
+            println!("{}", serde_json::to_string_pretty(&output)?);
+            std::process::exit(1);
+        }
+    }
+    result
+}
+
+/// The single object `--json` prints on stdout
+#[derive(Serialize, Debug)]
+struct JsonOutput<'a, T: Serialize> {
+    command: &'a str,
+    /// `ok` or `error`
+    status: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+/// Print a successful command's result as the `--json` object
+fn print_json<T: Serialize + ?Sized>(command: &str, result: &T) -> Result<()> {
+    let output = JsonOutput { command, status: "ok", result: Some(result), message: None };
+    println!("{}", serde_json::to_string_pretty(&output)?);
+    Ok(())
+}
+
+impl Commands {
+    /// The subcommand's name as typed on the command line
+    fn name(&self) -> &'static str {
+        match self {
+            Commands::Send(_) => "send",
+            Commands::Prepare(_) => "prepare",
+            Commands::Init { .. } => "init",
+            Commands::Template { .. } => "template",
+            Commands::Tree { .. } => "tree",
+            Commands::Verify { .. } => "verify",
+            Commands::Validate { .. } => "validate",
+            Commands::Config { .. } => "config",
+            Commands::History { .. } => "history",
+            Commands::Cache { .. } => "cache",
+            Commands::Fetch { .. } => "fetch",
+            Commands::Status { .. } => "status",
+            Commands::Clean { .. } => "clean",
+            Commands::Version => "version",
+            Commands::Update { .. } => "update",
+        }
+    }
+    
+    /// Whether the command has a `--json` result
+    fn supports_json(&self) -> bool {
+        matches!(
+            self,
+            Commands::Send(_)
+                | Commands::Tree { .. }
+                | Commands::Status { .. }
+                | Commands::Version
+                | Commands::History { action: None, .. }
+        )
+    }
+}
+
+fn run_command(cli: &Cli) -> Result<()> {
+    if cli.json && !cli.command.supports_json() {
+        return Err(anyhow::anyhow!("--json is not supported by `optimus {}`", cli.command.name()));
+    }
+    
+    match &cli.command {
+        Commands::Prepare(args) => run_prepare(args)?,
+        
+        Commands::Init { config, api_key, competition_id } => {
+            create_config_file(config, api_key.clone(), competition_id.clone())?;
+        },
+        
+        Commands::Validate { config } => run_validate(config)?,
+        
+        Commands::Tree { config, format } => {
+            // The tree works without a config file, using default exclusions
+            let config_data = if config_exists(config) {
+                Some(load_config(config)?)
+            } else {
+                None
+            };
+            
+            let collect = CollectOptions {
+                root: env::current_dir()?,
+                format: format.clone()
+                    .or_else(|| config_data.as_ref().and_then(|c| c.format.clone()))
+                    .unwrap_or_else(|| "repo".to_string()),
+                skip_paths: std::fs::canonicalize(config).into_iter().collect(),
+                ..Default::default()
+            };
+            let collect = match config_data {
+                Some(config_data) => CollectOptions {
+                    exclude: config_data.exclude,
+                    include: config_data.include,
+                    respect_gitignore: config_data.preferences.respect_gitignore,
+                    follow_symlinks: config_data.follow_symlinks,
+                    ..collect
+                },
+                None => collect,
+            };
+            
+            let entries = collect_entries(&collect)?;
+            if cli.json {
+                let (root, _, _) = build_tree(&entries)?;
+                print_json("tree", &root)?;
+            } else {
+                print_tree(&entries)?;
+            }
+        },
+        
+        Commands::Verify { archive, format, strict } => {
+            validate_format(format)?;
+            
+            println!("🔍 Verifying {} against the '{}' format", archive, format);
+            let problems = verify_archive(Path::new(archive), format, *strict)?;
+            
+            if !problems.is_empty() {
+                for problem in &problems {
+                    println!("❌ {}", problem);
+                }
+                return Err(anyhow::anyhow!("Archive failed verification with {} problem(s)", problems.len()));
+            }
+            
+            println!("✅ Archive satisfies the '{}' format", format);
+        },
+        
+        Commands::Config { action } => match action {
+            ConfigCommand::Edit { config } => edit_config(config)?,
+            ConfigCommand::Aliases { config } => list_aliases(&load_config(config)?),
+        },
+        
+        Commands::Cache { action } => match action {
+            CacheCommand::Info => print_cache_info()?,
+            CacheCommand::Clear { what } => clear_cache(*what)?,
+        },
+        
+        Commands::History { limit, action } => match action {
+            None if cli.json => {
+                let history = read_history()?;
+                print_json("history", &history[history.len().saturating_sub(*limit)..])?;
+            },
+            None => print_history(&read_history()?, *limit),
+            Some(HistoryCommand::Prune { keep, older_than, config }) => {
+                // Fall back to the configured limit only when no explicit rule is given
+                let keep = match (keep, older_than) {
+                    (Some(keep), _) => Some(*keep),
+                    (None, Some(_)) => None,
+                    (None, None) => Some(if config_exists(config) {
+                        load_config(config)?.preferences.history_limit
+                    } else {
+                        default_history_limit()
+                    }),
+                };
+                let cutoff = older_than.as_deref()
+                    .map(parse_duration)
+                    .transpose()?
+                    .map(|age| unix_now().saturating_sub(age.as_secs()));
+                
+                let history = read_history()?;
+                let before = history.len();
+                let kept = prune_history(history, keep, cutoff);
+                let removed = before - kept.len();
+                
+                if removed > 0 {
+                    write_history(&kept)?;
+                }
+                println!("🧹 Removed {} history records, {} remaining", removed, kept.len());
+            },
+        },
+        
+        Commands::Template { format, config } => {
+            scaffold_template(format, config)?;
+        },
+        
+        Commands::Status { config, competition_id, server, api_key, environment } => {
+            let mut config_data = load_layered_config(std::slice::from_ref(config), &[])?;
+            apply_environment(&mut config_data, environment.as_deref())?;
+            if let Some(api) = api_key {
+                config_data.api_key = api.clone();
+            }
+            ensure_api_key(&config_data)?;
+            
+            let competition = competition_id.as_deref()
+                .or(config_data.competition_id.as_deref())
+                .map(|id| resolve_competition_alias(&config_data, id));
+            let server_url = server.clone().unwrap_or_else(|| config_data.server_url.clone());
+            
+            let client = build_client(&config_data)?;
+            let (check_response, server_now) = fetch_check(&client, &server_url, &config_data, competition.as_deref())?;
+            
+            if cli.json {
+                print_json("status", &check_response)?;
+            } else {
+                print_status(&check_response, competition.as_deref(), server_now.unwrap_or_else(unix_now));
+            }
+        },
+        
+        Commands::Fetch { config, id, last: _, output, api_key, server, environment } => {
+            let mut config_data = load_layered_config(std::slice::from_ref(config), &[])?;
+            apply_environment(&mut config_data, environment.as_deref())?;
+
+            if let Some(api) = api_key {
+                config_data.api_key = api.clone();
+            }
+            ensure_api_key(&config_data)?;
+            let server_url = server.clone().unwrap_or_else(|| config_data.server_url.clone());
+
+            // Find the history record for the requested submission, or the latest one
+            let history = read_history()?;
+            let record = match id {
+                Some(id) => history.iter().rev().find(|e| e.submission_id.as_deref() == Some(id.as_str())),
+                None => history.iter().rev().find(|e| e.submission_id.is_some()),
+            };
+
+            let submission_id = match id {
+                Some(id) => id.clone(),
+                None => record
+                    .and_then(|e| e.submission_id.clone())
+                    .ok_or_else(|| anyhow::anyhow!(
+                        "No previous submission found in history. Use --id to specify one."
+                    ))?,
+            };
+
+            // The ID names the download URL and the default output file
+            validate_submission_id(&submission_id)?;
+            let output_path = match output {
+                Some(path) => PathBuf::from(path),
+                None => PathBuf::from(format!("submission-{}.zip", submission_id)),
+            };
+
+            let client = build_client(&config_data)?;
+            let expected_sha256 = record.and_then(|e| e.sha256.as_deref());
+            fetch_submission(&client, &server_url, &config_data, &submission_id, &output_path, expected_sha256)?;
+        },
+
+        Commands::Clean { dry_run, temp_dir } => run_clean(temp_dir.as_deref(), *dry_run)?,
+        
+        Commands::Version => {
+            let info = version_info();
+            if cli.json {
+                print_json("version", &info)?;
+            } else {
+                print_version_info(&info);
+            }
+        },
+        
+        Commands::Update { force, channel, config } => {
+            // The channel can come from the config, but updating never requires one
+            let channel = match channel {
+                Some(channel) => *channel,
+                None if config_exists(config) => load_config(config)?.update_channel,
+                None => UpdateChannel::default(),
+            };
+            match check_for_updates(channel)? {
+                Some((_, asset)) => {
+                    update_to_latest(&asset, *force)?;
+                },
+                None => {
+                    // No update available
+                },
+            }
+        },
+        
+        Commands::Send(args) => {
+            let started = Instant::now();
+            let mut summary = RunSummary::default();
+            let silent = args.silent || cli.quiet > 1;
+            let quiet = args.quiet_success || silent || cli.quiet > 0;
+            QUIET_OUTPUT.store(quiet, Ordering::Relaxed);
+            
+            let result = run_send(args, &mut summary);
+            
+            summary.elapsed_secs = started.elapsed().as_secs_f64();
+            summary.retries = RETRIES_USED.load(Ordering::SeqCst);
+            if let Err(err) = &result {
+                summary.status = RunStatus::Failed;
+                summary.error = Some(err.to_string());
+            }
+            
+            // Default to a summary only when someone is watching the terminal.
+            // Quiet runs replace it with a single line, but failures still get the full summary.
+            let show_summary = args.summary || (!args.no_summary && std::io::stdout().is_terminal());
+            if cli.json {
+                let output = JsonOutput {
+                    command: "send",
+                    status: if result.is_ok() { "ok" } else { "error" },
+                    result: Some(&summary),
+                    message: summary.error.clone(),
+                };
+                println!("{}", serde_json::to_string_pretty(&output)?);
+                if result.is_err() {
+                    close_log();
+                    std::process::exit(1);
+                }
+            } else if quiet && result.is_ok() {
+                if !silent {
+                    print_quiet_result(&summary);
+                }
+            } else if show_summary {
+                print_summary(&summary);
+            }
+            
+            result?;
+            
+            if summary.status == RunStatus::Throttled {
+                close_log();
+                std::process::exit(EXIT_THROTTLED);
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::MutexGuard;
+
+    /// Serializes tests that read or set process environment variables
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    fn lock_env() -> MutexGuard<'static, ()> {
+        ENV_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn parse_yaml_config(text: &str) -> SubmissionConfig {
+        let value: serde_yaml::Value = serde_yaml::from_str(text).unwrap();
+        config_from_value(value).unwrap()
+    }
+
+    #[test]
+    fn default_server_guard_fires_for_init_config() {
+        let _env = lock_env();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("submission.yml");
+        create_config_file(path.to_str().unwrap(), Some("key".to_string()), None).unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(!text.contains("server_url"));
+
+        let config = parse_yaml_config(&text);
+        assert!(!config.server_url_set);
+        assert!(needs_default_server_guard(&config.server_url, config.server_url_set, false));
+    }
+
+    #[test]
+    fn default_server_guard_quiet_for_explicit_localhost() {
+        let _env = lock_env();
+        let config = parse_yaml_config("api_key: key\nserver_url: \"http://localhost:3000\"\n");
+        assert!(config.server_url_set);
+        assert!(!needs_default_server_guard(&config.server_url, config.server_url_set, false));
+        assert!(!needs_default_server_guard(&default_server_url(), false, true));
+        assert!(!needs_default_server_guard("https://api.example.com", false, false));
+    }
+
+    #[test]
+    fn env_settings_apply_without_a_config_file() {
+        let _env = lock_env();
+        env::set_var("OPTIMUS_SERVER_URL", "https://env.example.com");
+        env::set_var("OPTIMUS_COMPETITION_ID", "42");
+        let config = load_layered_config(&[], &[]);
+        env::remove_var("OPTIMUS_SERVER_URL");
+        env::remove_var("OPTIMUS_COMPETITION_ID");
+
+        let config = config.unwrap();
+        assert_eq!(config.server_url, "https://env.example.com");
+        assert!(config.server_url_set);
+        assert_eq!(config.competition_id.as_deref(), Some("42"));
+    }
+
+    #[test]
+    fn env_settings_outrank_environment_overlay() {
+        let _env = lock_env();
+        let text = "api_key: key\nenvironments:\n  staging:\n    server_url: \"https://staging.example.com\"\n    competition_id: \"staging\"\n    format: \"py\"\n";
+
+        env::set_var("OPTIMUS_SERVER_URL", "https://env.example.com");
+        env::set_var("OPTIMUS_FORMAT", "repo");
+        let mut config = parse_yaml_config(text);
+        let applied = apply_environment(&mut config, Some("staging"));
+        env::remove_var("OPTIMUS_SERVER_URL");
+        env::remove_var("OPTIMUS_FORMAT");
+
+        applied.unwrap();
+        assert_eq!(config.server_url, "https://env.example.com");
+        assert_eq!(config.format.as_deref(), Some("repo"));
+        // Fields without an OPTIMUS_ variable still come from the overlay
+        assert_eq!(config.competition_id.as_deref(), Some("staging"));
+    }
+
+    fn archive_names(entries: &[ArchiveEntry], read_threads: usize) -> (Vec<String>, Vec<ManifestEntry>) {
+        let mut zip = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let files = write_entries(&mut zip, entries, FileOptions::default(), true, read_threads, &ProgressBar::hidden()).unwrap();
+        let mut archive = zip::ZipArchive::new(zip.finish().unwrap()).unwrap();
+        let names = (0..archive.len()).map(|i| archive.by_index(i).unwrap().name().to_string()).collect();
+        (names, files)
+    }
+
+    #[test]
+    fn threaded_reads_keep_sequential_entry_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let entries: Vec<ArchiveEntry> = (0..100)
+            .map(|i| {
+                let path = dir.path().join(format!("file{:03}.txt", i));
+                std::fs::write(&path, "x".repeat(i * 37)).unwrap();
+                ArchiveEntry { path, name: format!("file{:03}.txt", i), is_dir: false }
+            })
+            .collect();
+
+        let (sequential, sequential_files) = archive_names(&entries, 1);
+        let (threaded, threaded_files) = archive_names(&entries, 4);
+        assert_eq!(sequential, threaded);
+        assert_eq!(sequential.len(), 100);
+        let hashes = |files: &[ManifestEntry]| files.iter().map(|f| f.sha256.clone()).collect::<Vec<_>>();
+        assert_eq!(hashes(&sequential_files), hashes(&threaded_files));
+    }
+
+    #[test]
+    fn readers_wait_for_the_writer_window() {
+        let progress = WriterProgress::default();
+        // Entries inside the window never block
+        progress.wait_until_within(3, 4);
+
+        thread::scope(|scope| {
+            let reader = scope.spawn(|| progress.wait_until_within(10, 4));
+            thread::sleep(Duration::from_millis(50));
+            assert!(!reader.is_finished());
+            progress.advance(7);
+            reader.join().unwrap();
+        });
+    }
+
+    #[test]
+    fn mmap_and_buffered_digests_agree() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.bin");
+        let contents: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &contents).unwrap();
+
+        for algo in [HashAlgo::Sha256, HashAlgo::Sha512, HashAlgo::Blake3] {
+            let file = File::open(&path).unwrap();
+            let metadata = file.metadata().unwrap();
+            let expected = bytes_digest(&contents, algo);
+            assert_eq!(mmap_digest(&file, &metadata, algo).as_deref(), Some(expected.as_str()));
+            assert_eq!(buffered_digest(File::open(&path).unwrap(), algo).unwrap(), expected);
+            assert_eq!(file_digest(&path, algo, true).unwrap(), expected);
+            assert_eq!(file_digest(&path, algo, false).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn mmap_digest_falls_back_when_file_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.bin");
+        std::fs::write(&path, b"before").unwrap();
+        let before = std::fs::metadata(&path).unwrap();
+
+        // The file grows after its metadata was taken, as if written to during hashing
+        std::fs::write(&path, b"after, and longer").unwrap();
+        let file = File::open(&path).unwrap();
+        assert_eq!(mmap_digest(&file, &before, HashAlgo::Sha256), None);
+        assert_eq!(file_digest(&path, HashAlgo::Sha256, true).unwrap(), bytes_digest(b"after, and longer", HashAlgo::Sha256));
+    }
+
+    #[test]
+    fn only_gateway_errors_and_listed_statuses_are_retried() {
+        use reqwest::StatusCode;
+
+        for code in [502, 503, 504] {
+            assert!(is_retryable_status(&[], StatusCode::from_u16(code).unwrap()));
+        }
+        for code in [400, 401, 403, 404, 408, 429, 500] {
+            assert!(!is_retryable_status(&[], StatusCode::from_u16(code).unwrap()));
+        }
+        assert!(is_retryable_status(&[408, 429], StatusCode::REQUEST_TIMEOUT));
+        assert!(is_retryable_status(&[408, 429], StatusCode::TOO_MANY_REQUESTS));
+    }
+
+    #[test]
+    fn connect_errors_are_always_retried() {
+        // Bind then drop a listener so the port is very likely closed
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let err = Client::new().get(format!("http://127.0.0.1:{}/", port)).send().unwrap_err();
+        assert!(err.is_connect());
+
+        let err = anyhow::Error::from(err);
+        assert!(is_transient(&err, true));
+        assert!(is_transient(&err, false));
+    }
+
+    #[test]
+    fn timeouts_are_only_retried_when_idempotent() {
+        // Accepts the connection but never answers
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let err = Client::new().get(url).timeout(Duration::from_millis(100)).send().unwrap_err();
+        assert!(err.is_timeout());
+
+        let err = anyhow::Error::from(err);
+        assert!(is_transient(&err, true));
+        assert!(!is_transient(&err, false));
+        assert!(!is_transient(&anyhow::anyhow!("not a request error"), true));
+    }
+
+    #[test]
+    fn cache_targets_cover_state_files() {
+        let state = CacheTarget::State.paths().unwrap();
+        assert!(state.iter().any(|path| path.ends_with("upload_rate.json")));
+        assert!(state.iter().any(|path| path.ends_with("last_competition.json")));
+
+        let all = CacheTarget::All.paths().unwrap();
+        assert_eq!(all.len(), 4);
+        assert!(all.contains(&cache_dir().unwrap().join("check")));
+        assert!(state.iter().all(|path| all.contains(path)));
+    }
+
+    #[test]
+    fn layered_configs_override_and_concatenate() {
+        let _env = lock_env();
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("base.yml");
+        let secrets = dir.path().join("secrets.yml");
+        std::fs::write(&base, "api_key: base\ncompetition_id: \"c1\"\nexclude: [\"a\"]\n").unwrap();
+        std::fs::write(&secrets, "api_key: secret\nexclude: [\"b\"]\n").unwrap();
+        let paths = [base.to_string_lossy().to_string(), secrets.to_string_lossy().to_string()];
+
+        let config = load_layered_config(&paths, &[dir.path().join("missing.yml").to_string_lossy().to_string()]).unwrap();
+        assert_eq!(config.api_key, "secret");
+        assert_eq!(config.competition_id.as_deref(), Some("c1"));
+        assert_eq!(config.exclude, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn missing_explicit_config_is_an_error() {
+        let _env = lock_env();
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("base.yml");
+        std::fs::write(&base, "api_key: base\n").unwrap();
+        let missing = dir.path().join("secrets.yml").to_string_lossy().to_string();
+
+        let err = load_layered_config(std::slice::from_ref(&missing), &[]).unwrap_err();
+        assert!(err.to_string().contains("not found"));
+        assert!(load_layered_config(&[base.to_string_lossy().to_string(), missing], &[]).is_err());
+    }
+
+    #[test]
+    fn version_features_match_the_build() {
+        let features = enabled_features();
+        assert_eq!(features[0], "deflate");
+        assert_eq!(features.contains(&"bzip2"), cfg!(feature = "bzip2"));
+        assert_eq!(features.contains(&"zstd"), cfg!(feature = "zstd"));
+        assert_eq!(features.contains(&"socks"), cfg!(feature = "socks"));
+
+        assert_eq!(parse_compression_method("zstd").is_ok(), cfg!(feature = "zstd"));
+        assert_eq!(parse_compression_method("bzip2").is_ok(), cfg!(feature = "bzip2"));
+        assert_eq!(parse_proxy("socks5h://127.0.0.1:1080").is_ok(), cfg!(feature = "socks"));
+        assert!(parse_compression_method("deflate").is_ok());
+    }
+
+    /// A small tree whose directories have unusual modes on disk
+    fn tree_with_private_dirs() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for sub in ["b", "a", "a/c"] {
+            std::fs::create_dir_all(dir.path().join(sub)).unwrap();
+            std::fs::write(dir.path().join(sub).join("f.txt"), sub).unwrap();
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            for sub in ["a", "b"] {
+                std::fs::set_permissions(dir.path().join(sub), std::fs::Permissions::from_mode(0o700)).unwrap();
+            }
+        }
+        dir
+    }
+
+    fn test_collect(root: &Path) -> CollectOptions {
+        CollectOptions { root: root.to_path_buf(), format: "repo".to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn zip_directory_entries_are_sorted_with_fixed_mode() {
+        let tree = tree_with_private_dirs();
+        let temp = tempfile::tempdir().unwrap();
+        let settings = CompressionSettings { method: zip::CompressionMethod::Deflated, level: 6 };
+        let built = create_zip_archive(settings, &test_collect(tree.path()), 1, &EntryLayout::default(), temp.path(), false).unwrap();
+
+        let mut archive = zip::ZipArchive::new(File::open(&built.path).unwrap()).unwrap();
+        let mut names = Vec::new();
+        for i in 0..archive.len() {
+            let entry = archive.by_index(i).unwrap();
+            assert_eq!(entry.unix_mode().unwrap() & 0o7777, ENTRY_MODE, "{}", entry.name());
+            names.push(entry.name().to_string());
+        }
+        assert_eq!(names, ["a/", "a/c/", "a/c/f.txt", "a/f.txt", "b/", "b/f.txt"]);
+    }
 
-/// Create a new configuration file
-fn create_config_file(config_path: &str, api_key: Option<String>, competition_id: Option<String>) -> Result<()> {
-    let config_file = PathBuf::from(config_path);
-    
-    if config_file.exists() {
-        let overwrite = Confirm::with_theme(&ColorfulTheme::default())
-            .with_prompt(format!("Config file '{}' already exists. Overwrite?", config_path))
-            .default(false)
-            .interact()?;
-        
-        if !overwrite {
-            println!("❌ Config creation aborted.");
-            return Ok(());
+    #[test]
+    fn targz_directory_entries_are_sorted_with_fixed_mode() {
+        let tree = tree_with_private_dirs();
+        let temp = tempfile::tempdir().unwrap();
+        let settings = CompressionSettings { method: zip::CompressionMethod::Deflated, level: 6 };
+        let built = create_targz_archive(settings, &test_collect(tree.path()), &EntryLayout::default(), temp.path(), false).unwrap();
+
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(File::open(&built.path).unwrap()));
+        let mut names = Vec::new();
+        for entry in archive.entries().unwrap() {
+            let entry = entry.unwrap();
+            assert_eq!(entry.header().mode().unwrap() & 0o7777, ENTRY_MODE);
+            names.push(entry.path().unwrap().to_string_lossy().trim_end_matches('/').to_string());
         }
+        assert_eq!(names, ["a", "a/c", "a/c/f.txt", "a/f.txt", "b", "b/f.txt"]);
     }
-    
-    // Create a default config
-    let config = SubmissionConfig {
-        api_key: api_key.unwrap_or_else(|| "your-api-key-here".to_string()),
-        competition_id,
-        format: None,
-        server_url: default_server_url(),
-        compression_level: default_compression_level(),
-        exclude: vec![
-            ".git".to_string(),
-            ".DS_Store".to_string(), 
-            "node_modules".to_string(),
-            "target".to_string(),
-            ".env".to_string(),
-            "venv".to_string(),
-        ],
-        preferences: Preferences {
-            auto_confirm: false,
-            save_history: true,
-        },
-    };
-    
-    // Write the config to file
-    let file = File::create(config_file)?;
-    serde_yaml::to_writer(file, &config)?;
-    
-    println!("✅ Created configuration file: {}", config_path);
-    println!("   Please edit it to set your API key and other preferences.");
-    
-    Ok(())
-}
 
-/// Check with the server for submission approval and format requirements
-fn check_with_server(server_url: &str, api_key: &str, competition_id: Option<&str>) -> Result<CheckResponse> {
-    let mut check_url = format!("{}/check", server_url);
+    #[test]
+    fn walk_skips_leftover_archives_of_every_format() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["model.py", "old.zip", "old.tar.gz", "OLD.TAR.GZ", "data.gz"] {
+            std::fs::write(dir.path().join(name), "x").unwrap();
+        }
 
-    // Add competition_id query parameter if available
-    if let Some(comp_id) = competition_id {
-        check_url = format!("{}?competition={}", check_url, comp_id);
+        let mut skipped = Vec::new();
+        let entries = walk_entries(&test_collect(dir.path()), |path, reason| {
+            if matches!(reason, SkipReason::Archive) {
+                skipped.push(path.to_string_lossy().to_string());
+            }
+        }).unwrap();
+
+        let names: Vec<_> = entries.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, ["data.gz", "model.py"]);
+        skipped.sort();
+        assert_eq!(skipped, ["OLD.TAR.GZ", "old.tar.gz", "old.zip"]);
     }
 
-    println!("🔍 Checking with server: {}", check_url);
+    #[test]
+    fn parse_duration_accepts_units_and_rejects_overflow() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("1h30m").unwrap(), Duration::from_secs(5400));
+        assert_eq!(parse_duration(" 2d ").unwrap(), Duration::from_secs(172_800));
+        assert_eq!(parse_duration("1w1s").unwrap(), Duration::from_secs(604_801));
 
-    let client = Client::new();
-    let response = client.get(&check_url)
-        .header("Authorization", format!("Bearer {}", api_key))
-        .timeout(Duration::from_secs(10))
-        .send()?;
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("10x").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("5m3").is_err());
 
-    if !response.status().is_success() {
-        return Err(anyhow::anyhow!(
-            "Failed to check with server. Status: {}, Body: {}",
-            response.status(),
-            response.text().unwrap_or_default()
-        ));
+        let err = parse_duration("99999999999999999w").unwrap_err();
+        assert!(err.to_string().contains("too long"));
+        assert!(parse_duration(&format!("{}s1s", u64::MAX)).is_err());
     }
 
-    let check_response: CheckResponse = response.json()?;
+    fn manifest(files: &[(&str, &str)]) -> Manifest {
+        Manifest {
+            format: "repo".to_string(),
+            files: files.iter()
+                .map(|(path, sha256)| ManifestEntry { path: path.to_string(), size: 1, sha256: sha256.to_string() })
+                .collect(),
+        }
+    }
 
-    // Print information about the server response
-    println!("✅ Server requires format: {}", check_response.required_format);
+    #[test]
+    fn manifest_diff_lists_changed_and_deleted_files() {
+        let base = manifest(&[("a.py", "1"), ("b.py", "2"), ("gone.py", "3"), ("also_gone.py", "4")]);
+        let current = manifest(&[("new.py", "9"), ("a.py", "1"), ("b.py", "changed")]);
 
-    if check_response.submission_approved {
-        println!("✅ Submission approved. Remaining attempts: {}", check_response.remaining_attempts);
-    } else {
-        println!("❌ Submission not approved. No remaining attempts.");
+        let diff = diff_manifests(&base, &current);
+        assert_eq!(diff.changed, ["new.py", "b.py"]);
+        assert_eq!(diff.deleted, ["gone.py", "also_gone.py"]);
+
+        let same = diff_manifests(&base, &base);
+        assert!(same.changed.is_empty() && same.deleted.is_empty());
     }
 
-    if let Some(competition_name) = &check_response.competition_name {
-        println!("🏆 Competition: {}", competition_name);
+    #[test]
+    fn deterministic_builds_ignore_modification_times() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.py");
+        std::fs::write(&path, "print(1)").unwrap();
+        // 2001-02-03 04:05:06 UTC
+        let modified = UNIX_EPOCH + Duration::from_secs(981_173_106);
+        File::options().write(true).open(&path).unwrap().set_modified(modified).unwrap();
+
+        let build = |deterministic: bool| {
+            let mut buffer = std::io::Cursor::new(Vec::new());
+            ArchiveBuilder::new(dir.path()).deterministic(deterministic).build_to(&mut buffer).unwrap();
+            buffer.into_inner()
+        };
+
+        let first = build(true);
+        assert_eq!(first, build(true));
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(first)).unwrap();
+        let time = archive.by_name("model.py").unwrap().last_modified();
+        assert_eq!((time.year(), time.month(), time.day()), (1980, 1, 1));
+
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(build(false))).unwrap();
+        let time = archive.by_name("model.py").unwrap().last_modified();
+        assert_eq!(
+            (time.year(), time.month(), time.day(), time.hour(), time.minute(), time.second()),
+            (2001, 2, 3, 4, 5, 6)
+        );
     }
 
-    if let Some(last_submission) = check_response.last_submission_by_user {
-        let duration = SystemTime::now()
-            .duration_since(UNIX_EPOCH + Duration::from_secs(last_submission))
-            .unwrap_or(Duration::from_secs(0));
+    #[test]
+    fn archive_name_template_fills_placeholders() {
+        let context = ArchiveNameContext {
+            dir: "project",
+            competition: Some("comp-1"),
+            // 2024-03-05 UTC
+            timestamp: 1_709_640_000,
+            sha256: Some("0123456789abcdef"),
+            format: ArchiveFormat::Zip,
+        };
+        assert_eq!(
+            render_archive_name("{competition}-{dir}-{date}-{sha8}.zip", &context).unwrap(),
+            "comp-1-project-20240305-01234567.zip"
+        );
+        assert_eq!(render_archive_name("{timestamp}.zip", &context).unwrap(), "1709640000.zip");
+        assert_eq!(render_archive_name("plain.zip", &context).unwrap(), "plain.zip");
+        let targz = ArchiveNameContext { format: ArchiveFormat::Targz, ..context };
+        assert_eq!(render_archive_name("{dir}.tar.gz", &targz).unwrap(), "project.tar.gz");
+        assert_eq!(render_archive_name("{dir}.TGZ", &targz).unwrap(), "project.TGZ");
+    }
 
-        let hours = duration.as_secs() / 3600;
-        let minutes = (duration.as_secs() % 3600) / 60;
-        println!("📊 Last submission was {} hours and {} minutes ago", hours, minutes);
-    } else {
-        println!("📊 No previous submissions found");
+    #[test]
+    fn archive_name_template_rejects_bad_input() {
+        let context = ArchiveNameContext { dir: "project", competition: None, timestamp: 0, sha256: None, format: ArchiveFormat::Zip };
+        assert!(render_archive_name("{competition}.zip", &context).is_err());
+        assert!(render_archive_name("{sha8}.zip", &context).is_err());
+        assert!(render_archive_name("{nope}.zip", &context).is_err());
+        assert!(render_archive_name("{dir.zip", &context).is_err());
+        assert!(render_archive_name("../{dir}.zip", &context).is_err());
+        assert!(render_archive_name("", &context).is_err());
+        // The extension has to match the archive's format
+        assert!(render_archive_name("{dir}", &context).is_err());
+        assert!(render_archive_name("{dir}.tar.gz", &context).is_err());
+        let targz = ArchiveNameContext { format: ArchiveFormat::Targz, ..context };
+        let err = render_archive_name("{dir}.zip", &targz).unwrap_err();
+        assert!(err.to_string().contains("must end in .tar.gz or .tgz"), "{}", err);
     }
 
-    Ok(check_response)
-}
+    /// Archive entries with the given names, directories marked by a trailing `/`
+    fn named_entries(names: &[&str]) -> Vec<ArchiveEntry> {
+        names.iter()
+            .map(|name| ArchiveEntry {
+                path: PathBuf::from(name.trim_end_matches('/')),
+                name: name.trim_end_matches('/').to_string(),
+                is_dir: name.ends_with('/'),
+            })
+            .collect()
+    }
 
-/// Create a zip archive based on the specified format and exclusions
-fn create_zip_archive(compression: u8, format: &str, custom_exclusions: &[String]) -> Result<PathBuf> {
-    let current_dir = env::current_dir()?;
-    let dir_name = current_dir.file_name()
-        .context("Failed to get directory name")?
-        .to_string_lossy();
-    
-    let temp_dir = env::temp_dir();
-    let zip_path = temp_dir.join(format!("{}.zip", dir_name));
-    
-    // Delete the zip file if it already exists
-    if zip_path.exists() {
-        std::fs::remove_file(&zip_path)?;
+    fn entry_names(entries: &[ArchiveEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.name.as_str()).collect()
     }
-    
-    // Create a new zip file
-    let file = File::create(&zip_path)?;
-    let options = FileOptions::default()
-        .compression_method(zip::CompressionMethod::Deflated)
-        .unix_permissions(0o755)
-        .compression_level(Some(compression.into()));
-    
-    let mut zip = ZipWriter::new(file);
-    
-    // Common excluded directories and files
-    let mut excluded = vec![
-        ".git".to_string(), 
-        ".DS_Store".to_string(), 
-        "target".to_string(),
-        "node_modules".to_string(),
-        ".zip".to_string()
-    ];
-    
-    // Add custom exclusions
-    excluded.extend(custom_exclusions.iter().cloned());
-    
-    // Build include pattern based on format
-    let include_patterns: Vec<&str> = match format {
-        "py" => {
-            // Only include Python files and Python project files
-            println!("🐍 Using Python format: Only including Python files and project configuration");
-            vec![".py", "requirements.txt", "pyproject.toml", "setup.py", "setup.cfg", "Pipfile", "Pipfile.lock", "poetry.lock"]
-        },
-        _ => {
-            // Default "repo" format: include everything except excluded files
-            println!("📦 Using Repository format: Including all files except excluded ones");
-            vec![]
+
+    #[test]
+    fn strip_prefix_rebases_entries() {
+        let entries = named_entries(&["submission/", "submission/model.py", "submission/src/", "submission/src/a.py", "README.md"]);
+        let stripped = strip_entry_prefix(entries, "/submission/").unwrap();
+        assert_eq!(entry_names(&stripped), ["model.py", "src", "src/a.py", "README.md"]);
+    }
+
+    #[test]
+    fn strip_prefix_rejects_collisions() {
+        let entries = named_entries(&["submission/model.py", "model.py"]);
+        let err = strip_entry_prefix(entries, "submission").unwrap_err();
+        assert!(err.to_string().contains("collides"));
+
+        // Directories inside and outside the prefix merge
+        let entries = named_entries(&["submission/src/", "src/", "src/a.py"]);
+        assert_eq!(entry_names(&strip_entry_prefix(entries, "submission").unwrap()), ["src", "src/a.py"]);
+    }
+
+    #[test]
+    fn add_prefix_nests_entries_with_directory_entries() {
+        let entries = named_entries(&["model.py", "src/", "src/a.py"]);
+        let nested = add_entry_prefix(entries, "/team/run1/").unwrap();
+        assert_eq!(entry_names(&nested), ["team", "team/run1", "team/run1/model.py", "team/run1/src", "team/run1/src/a.py"]);
+        assert!(nested[0].is_dir && nested[1].is_dir && !nested[2].is_dir);
+
+        for bad in ["", "/", "../up", "a/../b", "./a"] {
+            assert!(add_entry_prefix(named_entries(&["model.py"]), bad).is_err(), "{}", bad);
         }
-    };
-    
-    println!("🔄 Creating zip archive...");
-    
-    // Walk through the directory tree and add files to the zip
-    for entry in WalkDir::new(&current_dir).into_iter().filter_map(|e| e.ok()) {
-        let path = entry.path();
-        let path_str = path.to_string_lossy();
-        
-        // Skip excluded directories and files
-        if excluded.iter().any(|e| path_str.contains(e)) {
-            continue;
+    }
+
+    #[test]
+    fn layout_strips_before_adding() {
+        let layout = EntryLayout { strip_prefix: Some("submission".to_string()), add_prefix: Some("out".to_string()) };
+        let entries = apply_layout(named_entries(&["submission/", "submission/model.py"]), &layout).unwrap();
+        assert_eq!(entry_names(&entries), ["out", "out/model.py"]);
+    }
+
+    #[test]
+    fn only_the_config_in_use_is_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        for name in ["model.py", "custom.yml", "submission.yml", "sub/custom.yml"] {
+            std::fs::write(dir.path().join(name), name).unwrap();
         }
-        
-        // Skip submission.yml
-        if path_str.ends_with("submission.yml") {
-            continue;
+
+        let mut collect = test_collect(dir.path());
+        collect.skip_paths = vec![std::fs::canonicalize(dir.path().join("custom.yml")).unwrap()];
+        collect.exclude_empty_dirs = true;
+        let mut skipped = Vec::new();
+        let entries = walk_entries(&collect, |path, reason| {
+            if matches!(reason, SkipReason::ConfigFile) {
+                skipped.push(path.to_path_buf());
+            }
+        }).unwrap();
+
+        let names = entry_names(&entries);
+        assert_eq!(names, ["model.py", "sub", "sub/custom.yml", "submission.yml"]);
+        assert_eq!(skipped, [PathBuf::from("custom.yml")]);
+
+        // With nothing to skip (e.g. --include-config), the config is archived like any file
+        collect.skip_paths.clear();
+        assert!(entry_names(&collect_entries(&collect).unwrap()).contains(&"custom.yml"));
+    }
+
+    fn auth_headers(config_yaml: &str) -> Result<reqwest::header::HeaderMap> {
+        let config = parse_yaml_config(config_yaml);
+        let request = authorize(Client::new().get("http://localhost/check"), &config)?.build()?;
+        Ok(request.headers().clone())
+    }
+
+    #[test]
+    fn auth_schemes_build_the_expected_headers() {
+        let _env = lock_env();
+
+        let headers = auth_headers("api_key: key\n").unwrap();
+        assert_eq!(headers["authorization"], "Bearer key");
+
+        let headers = auth_headers("api_key: key\nauth_scheme: basic\nusername: user\n").unwrap();
+        assert_eq!(headers["authorization"], "Basic dXNlcjprZXk=");
+
+        let headers = auth_headers("api_key: key\nauth_scheme: header\n").unwrap();
+        assert_eq!(headers["x-api-key"], "key");
+        assert!(!headers.contains_key("authorization"));
+
+        let headers = auth_headers("api_key: key\nauth_scheme: header\nauth_header: X-Token\n").unwrap();
+        assert_eq!(headers["x-token"], "key");
+
+        let headers = auth_headers("auth_scheme: none\n").unwrap();
+        assert!(headers.is_empty());
+    }
+
+    #[test]
+    fn basic_auth_requires_a_username() {
+        let _env = lock_env();
+        let err = auth_headers("api_key: key\nauth_scheme: basic\n").unwrap_err();
+        assert!(err.to_string().contains("username"));
+    }
+
+    /// `model.py`, `a/one.py`, `a/b/two.py` and `a/b/c/three.py`
+    fn nested_tree() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("a/b/c")).unwrap();
+        for name in ["model.py", "a/one.py", "a/b/two.py", "a/b/c/three.py"] {
+            std::fs::write(dir.path().join(name), name).unwrap();
         }
-        
-        // Skip if the path is the same as the current directory
-        if path == current_dir {
-            continue;
+        dir
+    }
+
+    #[test]
+    fn max_depth_limits_the_walk() {
+        let tree = nested_tree();
+        let mut collect = test_collect(tree.path());
+
+        collect.max_depth = Some(1);
+        let mut too_deep = Vec::new();
+        let entries = walk_entries(&collect, |path, reason| {
+            if matches!(reason, SkipReason::TooDeep) {
+                too_deep.push(path.to_path_buf());
+            }
+        }).unwrap();
+        assert_eq!(entry_names(&entries), ["a", "model.py"]);
+        assert!(!too_deep.is_empty());
+
+        collect.max_depth = Some(2);
+        assert_eq!(entry_names(&collect_entries(&collect).unwrap()), ["a", "a/b", "a/one.py", "model.py"]);
+
+        collect.max_depth = None;
+        assert_eq!(collect_entries(&collect).unwrap().len(), 7);
+    }
+
+    #[test]
+    fn exclude_empty_dirs_drops_dirs_without_files() {
+        let entries = named_entries(&["a/", "a/b/", "a/b/model.py", "empty/", "empty/nested/", "top.py"]);
+        assert_eq!(entry_names(&drop_empty_dirs(entries)), ["a", "a/b", "a/b/model.py", "top.py"]);
+
+        // A directory whose only files were filtered out is empty too
+        let tree = nested_tree();
+        let mut collect = test_collect(tree.path());
+        collect.exclude = vec!["three.py".to_string()];
+        assert!(entry_names(&collect_entries(&collect).unwrap()).contains(&"a/b/c"));
+        collect.exclude_empty_dirs = true;
+        assert!(!entry_names(&collect_entries(&collect).unwrap()).contains(&"a/b/c"));
+    }
+
+    #[test]
+    fn hash_algorithms_match_known_digests() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("abc.txt");
+        std::fs::write(&path, "abc").unwrap();
+
+        assert_eq!(
+            file_digest(&path, HashAlgo::Sha256, false).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            file_digest(&path, HashAlgo::Sha512, false).unwrap(),
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"
+        );
+        assert_eq!(
+            file_digest(&path, HashAlgo::Blake3, false).unwrap(),
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+    }
+
+    #[test]
+    fn hash_algo_comes_from_config() {
+        let _env = lock_env();
+        assert_eq!(parse_yaml_config("api_key: key\n").hash_algo, HashAlgo::Sha256);
+        let config = parse_yaml_config("api_key: key\nhash_algo: blake3\n");
+        assert_eq!(config.hash_algo, HashAlgo::Blake3);
+        assert_eq!(config.hash_algo.as_str(), "blake3");
+    }
+
+    #[test]
+    fn log_file_is_gzipped_and_appendable() {
+        let dir = tempfile::tempdir().unwrap();
+        let gz_path = dir.path().join("requests.jsonl.gz");
+        let plain_path = dir.path().join("requests.jsonl");
+
+        // Two runs appending to the same compressed log read back as one stream
+        for run in ["first", "second"] {
+            open_log(gz_path.to_str().unwrap()).unwrap();
+            log_record("check", "http://localhost/check", 200, run);
+            close_log();
         }
-        
-        let name = path.strip_prefix(&current_dir)?;
-        
-        // For Python format, only include specific file types
-        if format == "py" && path.is_file() {
-            let should_include = include_patterns.iter()
-                .any(|pattern| path_str.ends_with(pattern));
-            
-            if !should_include {
-                continue;
+        open_log(plain_path.to_str().unwrap()).unwrap();
+        log_record("submit", "http://localhost/submit", 500, "plain");
+        close_log();
+
+        let mut text = String::new();
+        flate2::read::MultiGzDecoder::new(File::open(&gz_path).unwrap()).read_to_string(&mut text).unwrap();
+        let bodies: Vec<String> = text.lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["body"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(bodies, ["first", "second"]);
+
+        let plain: serde_json::Value = serde_json::from_str(std::fs::read_to_string(&plain_path).unwrap().trim()).unwrap();
+        assert_eq!(plain["kind"], "submit");
+        assert_eq!(plain["status"], 500);
+    }
+
+    /// Serve one HTTP request with `status` and an empty body, returning the server's URL
+    /// and the request line it received
+    fn serve_once(status: u16) -> (String, thread::JoinHandle<String>) {
+        serve_once_with_body(status, "")
+    }
+
+    fn serve_once_with_body(status: u16, body: &'static str) -> (String, thread::JoinHandle<String>) {
+        use std::io::{BufRead, BufReader};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
             }
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 {} X\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            ).unwrap();
+            request_line.trim().to_string()
+        });
+        (url, handle)
+    }
+
+    fn health_config(health_path: &str) -> SubmissionConfig {
+        let _env = lock_env();
+        parse_yaml_config(&format!("health_path: {}\n", health_path))
+    }
+
+    #[test]
+    fn health_check_accepts_success_and_missing_endpoint() {
+        let config = health_config("/health");
+        for status in [200, 204, 404] {
+            let (url, server) = serve_once(status);
+            check_server_health(&Client::new(), &url, &config).unwrap();
+            assert_eq!(server.join().unwrap(), "GET /health HTTP/1.1");
         }
-        
-        // If the path is a file, add it to the zip
-        if path.is_file() {
-            let mut file = File::open(path)?;
-            zip.start_file(name.to_string_lossy(), options)?;
-            let mut buffer = Vec::new();
-            file.read_to_end(&mut buffer)?;
-            zip.write_all(&buffer)?;
-        } else if path.is_dir() && !name.as_os_str().is_empty() {
-            // If the path is a directory, add it as a directory entry to the zip
-            zip.add_directory(name.to_string_lossy(), options)?;
+    }
+
+    #[test]
+    fn health_check_fails_on_errors_and_unreachable_servers() {
+        for status in [500, 503] {
+            let (url, server) = serve_once(status);
+            let err = check_server_health(&Client::new(), &url, &health_config("status")).unwrap_err();
+            assert!(err.to_string().contains("--no-preflight"));
+            assert_eq!(server.join().unwrap(), "GET /status HTTP/1.1");
         }
+
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let err = check_server_health(&Client::new(), &format!("http://127.0.0.1:{}", port), &health_config("/health")).unwrap_err();
+        assert!(err.to_string().contains("unreachable"));
     }
-    
-    // Finish writing the zip file
-    zip.finish()?;
-    
-    Ok(zip_path)
-}
 
-/// Send the zip file to the endpoint
-fn send_zip_to_endpoint(zip_path: &Path, api_key: &str, submit_url: &str, competition_id: Option<&str>) -> Result<()> {
-    let file = File::open(zip_path)?;
-    let mut zip_content = Vec::new();
-    
-    // Read the zip file into memory
-    let mut reader = std::io::BufReader::new(file);
-    reader.read_to_end(&mut zip_content)?;
-    
-    println!("📦 Sending zip file to server: {}", submit_url);
-    
-    // Create a multipart form with the zip file
-    let file_name = zip_path.file_name()
-        .context("Failed to get zip file name")?
-        .to_string_lossy();
-    
-    let mut form = multipart::Form::new()
-        .part("file", multipart::Part::bytes(zip_content)
-            .file_name(file_name.to_string())
-            .mime_str("application/zip")?);
-    
-    // Add competition_id if available
-    if let Some(comp_id) = competition_id {
-        form = form.text("competition", comp_id.to_string());
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C").arg(dir)
+            .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?}", args);
     }
-    
-    // Send the POST request with the API key in the header
-    let client = Client::new();
-    let response = client.post(submit_url)
-        .header("Authorization", format!("Bearer {}", api_key))
-        .multipart(form)
-        .send()?;
-    
-    // Check if the request was successful
-    if response.status().is_success() {
-        println!("✅ Successfully sent the zip file to the server!");
-        println!("   Response: {}", response.text()?);
-    } else {
-        return Err(anyhow::anyhow!(
-            "Failed to send zip file to endpoint. Status: {}, Body: {}",
-            response.status(),
-            response.text().unwrap_or_default()
-        ));
+
+    #[test]
+    fn clean_git_check_blocks_dirty_trees() {
+        let dir = tempfile::tempdir().unwrap();
+        // Outside a repository the check only fails when a repository is required
+        check_clean_git(dir.path(), false).unwrap();
+        assert!(check_clean_git(dir.path(), true).is_err());
+
+        git(dir.path(), &["init", "-q"]);
+        std::fs::write(dir.path().join("model.py"), "v1").unwrap();
+        git(dir.path(), &["add", "model.py"]);
+        git(dir.path(), &["commit", "-q", "-m", "init"]);
+        check_clean_git(dir.path(), true).unwrap();
+
+        std::fs::write(dir.path().join("model.py"), "v2").unwrap();
+        std::fs::write(dir.path().join("new.py"), "new").unwrap();
+        let err = check_clean_git(dir.path(), false).unwrap_err();
+        assert!(err.to_string().contains("2 uncommitted or untracked"));
     }
-    
-    // Clean up the temporary zip file
-    std::fs::remove_file(zip_path)?;
-    
-    Ok(())
-}
 
-/// Check for the latest version available on GitHub
-fn check_for_updates() -> Result<Option<(Version, String)>> {
-    let current_version = Version::parse(env!("CARGO_PKG_VERSION"))?;
-    println!("🔄 Checking for updates... Current version: {}", current_version);
-    
-    // Get the repository URL from Cargo.toml metadata
-    let repository = env!("CARGO_PKG_REPOSITORY")
-        .trim_end_matches(".git")
-        .trim_end_matches('/');
-    
-    // Extract owner and repo name from the URL
-    let repo_parts: Vec<&str> = repository.split('/').collect();
-    let (owner, repo) = if repo_parts.len() >= 2 {
-        (repo_parts[repo_parts.len() - 2], repo_parts[repo_parts.len() - 1])
-    } else {
-        return Err(anyhow::anyhow!("Invalid repository URL format in Cargo.toml"));
-    };
-    
-    let github_api_url = format!("https://api.github.com/repos/{}/{}/releases/latest", owner, repo);
-    println!("🔍 Checking GitHub API: {}", github_api_url);
-    
-    let client = Client::new();
-    let response = client.get(&github_api_url)
-        .header("User-Agent", "Optimus CLI")
-        .send()?;
-    
-    // Handle 404 status specifically (no releases found)
-    if response.status() == reqwest::StatusCode::NOT_FOUND {
-        println!("❓ No official releases found for this project yet.");
-        return Ok(None);
-    } else if !response.status().is_success() {
-        return Err(anyhow::anyhow!(
-            "Failed to check for updates. Status: {}",
-            response.status()
-        ));
+    #[test]
+    fn manifest_compliance_reports_missing_and_extra_files() {
+        let expected: ExpectedManifest = serde_yaml::from_str(
+            "required: [model.py, weights.bin]\nallowed: [README.md, data/]\n"
+        ).unwrap();
+        let entries = named_entries(&[
+            "data/", "data/train.csv", "data/nested/val.csv", "model.py", "README.md", "notes.txt", "datasets.csv",
+        ]);
+        let mismatch = check_manifest_compliance(&expected, &entries);
+        assert_eq!(mismatch.missing, ["weights.bin"]);
+        assert_eq!(mismatch.extra, ["datasets.csv", "notes.txt"]);
+
+        let entries = named_entries(&["model.py", "weights.bin"]);
+        assert!(check_manifest_compliance(&expected, &entries).is_empty());
+    }
+
+    #[test]
+    fn retry_on_is_read_and_validated() {
+        let _env = lock_env();
+        let config = parse_yaml_config("competition_id: comp\nretry_on: [408, 429]\n");
+        assert_eq!(config.retry_on, [408, 429]);
+        assert!(is_retryable_status(&config.retry_on, reqwest::StatusCode::TOO_MANY_REQUESTS));
+
+        let value: serde_yaml::Value = serde_yaml::from_str("retry_on: [429, 600]\n").unwrap();
+        let err = config_from_value(value).unwrap_err();
+        assert!(err.to_string().contains("Invalid retry_on status code 600"));
+        assert!(validate_retry_on(&[99]).is_err());
+        assert!(validate_retry_on(&[100, 599]).is_ok());
+    }
+
+    #[test]
+    fn excludes_match_whole_path_components() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["target/out.bin", "targeting/plan.py", "src/target/gen.py", "target_notes.txt", "model.py"] {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, name).unwrap();
+        }
+        let mut collect = test_collect(dir.path());
+        collect.exclude = vec!["target".to_string()];
+        let mut excluded = Vec::new();
+        let entries = walk_entries(&collect, |path, reason| {
+            if let SkipReason::Excluded(pattern) = reason {
+                excluded.push((path.to_path_buf(), pattern.clone()));
+            }
+        }).unwrap();
+        assert_eq!(
+            entry_names(&entries),
+            ["model.py", "src", "target_notes.txt", "targeting", "targeting/plan.py"],
+        );
+        assert_eq!(excluded, [
+            (PathBuf::from("src/target"), "target".to_string()),
+            (PathBuf::from("target"), "target".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn gitignore_rules_apply_when_enabled() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("logs")).unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join(".gitignore"), "logs/\n*.tmp\n").unwrap();
+        std::fs::write(dir.path().join("src/.gitignore"), "local.py\n").unwrap();
+        for name in ["model.py", "scratch.tmp", "logs/run.log", "src/local.py", "src/train.py"] {
+            std::fs::write(dir.path().join(name), name).unwrap();
+        }
+
+        let mut collect = test_collect(dir.path());
+        assert!(entry_names(&collect_entries(&collect).unwrap()).contains(&"logs/run.log"));
+
+        collect.respect_gitignore = true;
+        let mut ignored = Vec::new();
+        let entries = walk_entries(&collect, |path, reason| {
+            if matches!(reason, SkipReason::GitIgnored) {
+                ignored.push(path.to_path_buf());
+            }
+        }).unwrap();
+        let names = entry_names(&entries);
+        assert!(names.contains(&"model.py") && names.contains(&"src/train.py"));
+        for name in ["logs", "logs/run.log", "scratch.tmp", "src/local.py"] {
+            assert!(!names.contains(&name), "{} should be ignored", name);
+        }
+        assert!(ignored.contains(&PathBuf::from("src/local.py")));
     }
-    
-    let release: GithubRelease = response.json()?;
-    
-    // Strip 'v' prefix if present
-    let version_str = release.tag_name.trim_start_matches('v');
-    let latest_version = Version::parse(version_str)?;
-    
-    // Find the appropriate asset based on platform and preferred file types
-    let asset = if cfg!(windows) {
-        // For Windows, prefer .exe, .msi, .bat or .ps1 installers
-        release.assets.iter()
-            .find(|asset| asset.name.ends_with(".exe") || asset.name.ends_with(".msi"))
-            .or_else(|| release.assets.iter().find(|asset| asset.name.ends_with(".bat") || asset.name.ends_with(".cmd")))
-            .or_else(|| release.assets.iter().find(|asset| asset.name.ends_with(".ps1")))
-            .or_else(|| release.assets.iter().find(|asset| asset.name.ends_with(".zip")))
-    } else if cfg!(unix) {
-        // For Unix, prefer shell scripts
-        release.assets.iter()
-            .find(|asset| asset.name.contains("direct-install"))
-            .or_else(|| release.assets.iter().find(|asset| asset.name.ends_with(".sh")))
-            .or_else(|| release.assets.iter().find(|asset| asset.name.ends_with(".bash") || asset.name.ends_with(".zsh")))
-            .or_else(|| release.assets.iter().find(|asset| asset.name.ends_with(".tar.gz") || asset.name.ends_with(".tgz")))
-    } else {
-        // For other platforms, just try to find a common installer format
-        release.assets.iter()
-            .find(|asset| asset.name.contains("install") || asset.name.contains("setup"))
+
+    #[test]
+    fn exclude_globs_match_relative_paths() {
+        let patterns = PathPatterns::new(
+            ["*.log", "**/cache", "docs/*.pdf", "/", ""].iter().map(|p| p.to_string()).collect(),
+        ).unwrap();
+        let matching = |path: &str| patterns.matching(Path::new(path));
+
+        assert_eq!(matching("run.log"), Some("*.log"));
+        assert_eq!(matching("a/b/run.log"), Some("*.log"));
+        assert_eq!(matching("data/cache"), Some("**/cache"));
+        assert_eq!(matching("docs/paper.pdf"), Some("docs/*.pdf"));
+        // `*` stops at `/`, and patterns with a `/` are anchored at the root
+        assert_eq!(matching("docs/old/paper.pdf"), None);
+        assert_eq!(matching("src/docs/paper.pdf"), None);
+        assert_eq!(matching("run.log.txt"), None);
+
+        assert!(PathPatterns::new(vec!["[".to_string()]).is_err());
     }
-    .ok_or_else(|| anyhow::anyhow!("No suitable installation file found for your platform in the latest release"))?;
-    
-    if latest_version > current_version {
-        println!("📦 New version available: {} (current: {})", latest_version, current_version);
-        Ok(Some((latest_version, asset.browser_download_url.clone())))
-    } else {
-        println!("✅ You have the latest version: {}", current_version);
-        Ok(None)
+
+    #[test]
+    fn rebuilding_a_touched_tree_gives_identical_archives() {
+        let tree = nested_tree();
+        let out = tempfile::tempdir().unwrap();
+        let collect = test_collect(tree.path());
+        let compression = compression_for_goal(OptimizeFor::Speed);
+        let build = || {
+            let zip = create_zip_archive(compression, &collect, 1, &EntryLayout::default(), out.path(), false).unwrap();
+            let targz = create_targz_archive(compression, &collect, &EntryLayout::default(), out.path(), false).unwrap();
+            (sha256_file(&zip.path).unwrap(), sha256_file(&targz.path).unwrap())
+        };
+
+        let first = build();
+        let touched = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        File::options().write(true).open(tree.path().join("a/one.py")).unwrap().set_modified(touched).unwrap();
+        assert_eq!(first, build());
     }
-}
 
-/// Download and install the latest version
-fn update_to_latest(download_url: &str, force: bool) -> Result<()> {
-    // Create a temporary directory to store the download
-    let temp_dir = tempdir()?;
+    #[test]
+    fn update_downloads_are_checked_against_the_published_sha256() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("direct-install.sh");
+        std::fs::write(&path, "hello").unwrap();
+        let digest = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
 
-    // Get filename from URL
-    let url_parts: Vec<&str> = download_url.split('/').collect();
-    let filename = url_parts.last()
-        .ok_or_else(|| anyhow::anyhow!("Invalid download URL"))?;
+        verify_published_sha256(&path, digest).unwrap();
+        verify_published_sha256(&path, &format!("{}  direct-install.sh\n", digest.to_uppercase())).unwrap();
 
-    let download_path = temp_dir.path().join(filename);
+        let err = verify_published_sha256(&path, &"0".repeat(64)).unwrap_err();
+        assert!(err.to_string().contains("Checksum mismatch"));
+        assert!(verify_published_sha256(&path, " \n").is_err());
+    }
 
-    println!("📥 Downloading latest version from {}...", download_url);
+    #[test]
+    fn config_strings_expand_environment_variables() {
+        let _env = lock_env();
+        env::set_var("OPTIMUS_TEST_HOST", "example.com");
+        env::remove_var("OPTIMUS_TEST_UNSET");
 
-    // Download the installation file
-    let mut response = Client::new().get(download_url)
-        .header("User-Agent", "Optimus CLI")
-        .send()?;
+        assert_eq!(
+            interpolate_env("https://${OPTIMUS_TEST_HOST}/api/$OPTIMUS_TEST_HOST.v1", "server_url").unwrap(),
+            "https://example.com/api/example.com.v1"
+        );
+        assert_eq!(interpolate_env("pa$$word $ end$", "api_key").unwrap(), "pa$word $ end$");
 
-    let mut file = File::create(&download_path)?;
-    copy(&mut response, &mut file)?;
+        let err = interpolate_env("$OPTIMUS_TEST_UNSET", "api_key").unwrap_err();
+        assert!(err.to_string().contains("'api_key' references $OPTIMUS_TEST_UNSET"));
+        assert!(interpolate_env("${OPTIMUS_TEST_HOST", "server_url").is_err());
 
-    // Make shell scripts executable on Unix platforms
-    #[cfg(unix)]
-    if filename.ends_with(".sh") {
-        use std::os::unix::fs::PermissionsExt;
-        let mut perms = std::fs::metadata(&download_path)?.permissions();
-        perms.set_mode(0o755);
-        std::fs::set_permissions(&download_path, perms)?;
+        let config = parse_yaml_config("competition_id: comp\nserver_url: https://${OPTIMUS_TEST_HOST}\n");
+        assert_eq!(config.server_url, "https://example.com");
+        env::remove_var("OPTIMUS_TEST_HOST");
     }
 
-    // Prompt for confirmation unless force flag is set
-    if !force {
-        let confirm = Confirm::with_theme(&ColorfulTheme::default())
-            .with_prompt("Ready to install the latest version. Continue?")
-            .default(true)
-            .interact()?;
+    #[test]
+    fn optimus_api_key_outranks_environment_keys() {
+        let _env = lock_env();
+        let text = "environments:\n  staging:\n    api_key: staging-key\n  prod:\n    api_key_env: OPTIMUS_TEST_PROD_KEY\n";
+        env::remove_var("OPTIMUS_TEST_PROD_KEY");
 
-        if !confirm {
-            println!("❌ Update cancelled.");
-            return Ok(());
+        env::set_var("OPTIMUS_API_KEY", "env-key");
+        let mut staging = parse_yaml_config(text);
+        let staging_applied = apply_environment(&mut staging, Some("staging"));
+        // The unset api_key_env variable is never read
+        let mut prod = parse_yaml_config(text);
+        let prod_applied = apply_environment(&mut prod, Some("prod"));
+        env::remove_var("OPTIMUS_API_KEY");
+
+        staging_applied.unwrap();
+        prod_applied.unwrap();
+        assert_eq!(staging.api_key, "env-key");
+        assert_eq!(prod.api_key, "env-key");
+
+        let mut staging = parse_yaml_config(text);
+        apply_environment(&mut staging, Some("staging")).unwrap();
+        assert_eq!(staging.api_key, "staging-key");
+        let mut prod = parse_yaml_config(text);
+        assert!(apply_environment(&mut prod, Some("prod")).is_err());
+    }
+
+    #[test]
+    fn server_commands_require_an_api_key() {
+        let _env = lock_env();
+        let mut config = parse_yaml_config("competition_id: comp\n");
+        assert!(ensure_api_key(&config).unwrap_err().to_string().contains("No API key provided"));
+        config.auth_scheme = AuthScheme::None;
+        ensure_api_key(&config).unwrap();
+        config.auth_scheme = AuthScheme::Bearer;
+        config.api_key = "key".to_string();
+        ensure_api_key(&config).unwrap();
+    }
+
+    #[test]
+    fn archive_size_limit_and_sizes_parse() {
+        assert_eq!(parse_size("500").unwrap(), 500);
+        assert_eq!(parse_size("500KB").unwrap(), 500 << 10);
+        assert_eq!(parse_size(" 50 mb ").unwrap(), 50 << 20);
+        assert_eq!(parse_size("1.5GB").unwrap(), 3 << 29);
+        for bad in ["", "MB", "50XB", "-1MB", "NaN"] {
+            assert!(parse_size(bad).is_err(), "{:?} should be rejected", bad);
         }
+
+        check_archive_size(10 << 20, None).unwrap();
+        check_archive_size(10 << 20, Some(10 << 20)).unwrap();
+        let err = check_archive_size((10 << 20) + 1, Some(10 << 20)).unwrap_err();
+        assert!(err.to_string().contains("over the 10.0 MB (10485760 bytes) limit"));
     }
 
-    println!("🔄 Installing latest version...");
+    #[test]
+    fn include_list_replaces_the_format_filter() {
+        let tree = nested_tree();
+        std::fs::write(tree.path().join("a/b/notes.md"), "notes").unwrap();
+        let mut collect = test_collect(tree.path());
+        collect.format = "py".to_string();
+        collect.include = vec!["*.md".to_string(), "a/one.py".to_string()];
+        collect.exclude = vec!["c".to_string()];
+
+        let mut not_included = Vec::new();
+        let entries = walk_entries(&collect, |path, reason| {
+            if matches!(reason, SkipReason::NotIncluded) {
+                not_included.push(path.to_path_buf());
+            }
+        }).unwrap();
+        // Only directories leading to included files are kept, and excludes still apply
+        assert_eq!(entry_names(&entries), ["a", "a/b", "a/b/notes.md", "a/one.py"]);
+        assert!(not_included.contains(&PathBuf::from("model.py")));
+        assert!(!not_included.contains(&PathBuf::from("a/b/c/three.py")));
+    }
 
-    // Handle different file types for different platforms
     #[cfg(unix)]
-    let result = handle_unix_update(&download_path, filename);
+    #[test]
+    fn symlinks_are_skipped_or_followed_inside_the_root() {
+        use std::os::unix::fs::symlink;
 
-    #[cfg(windows)]
-    let result = handle_windows_update(&download_path, filename);
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("secret.txt"), "secret").unwrap();
+        let tree = nested_tree();
+        symlink(tree.path().join("model.py"), tree.path().join("link.py")).unwrap();
+        symlink(tree.path().join("a/b"), tree.path().join("b_link")).unwrap();
+        symlink(outside.path().join("secret.txt"), tree.path().join("escape.txt")).unwrap();
+        symlink(outside.path(), tree.path().join("escape_dir")).unwrap();
 
-    // Use a generic fallback for other platforms
-    #[cfg(not(any(unix, windows)))]
-    let result = handle_generic_update(&download_path, filename);
+        let walk = |follow_symlinks: bool| {
+            let mut collect = test_collect(tree.path());
+            collect.follow_symlinks = follow_symlinks;
+            let mut skipped = Vec::new();
+            let entries = walk_entries(&collect, |path, reason| {
+                skipped.push((path.to_string_lossy().into_owned(), reason));
+            }).unwrap();
+            (entry_names(&entries).into_iter().map(str::to_string).collect::<Vec<_>>(), skipped)
+        };
 
-    result
-}
+        let (names, skipped) = walk(false);
+        assert!(!names.iter().any(|name| name.contains("link") || name.contains("escape")));
+        assert_eq!(skipped.iter().filter(|(_, reason)| *reason == SkipReason::Symlink).count(), 4);
 
-#[cfg(unix)]
-fn handle_unix_update(download_path: &Path, filename: &str) -> Result<()> {
-    let status = if filename.ends_with(".sh") {
-        // Run the shell script directly
-        Command::new(download_path).status()?
-    } else if filename.ends_with(".bash") || filename.ends_with(".zsh") {
-        // Run with appropriate shell
-        let shell = if filename.ends_with(".bash") { "bash" } else { "zsh" };
-        Command::new(shell).arg(download_path).status()?
-    } else if filename.ends_with(".zip") || filename.ends_with(".tar.gz") || filename.ends_with(".tgz") {
-        // For archives, ask the user to extract manually
-        println!("📦 Downloaded archive. Manual extraction and installation required.");
-        println!("   Download saved to: {}", download_path.display());
-        return Ok(());
-    } else {
-        // For any other file type, inform the user
-        println!("📄 Downloaded file: {}", download_path.display());
-        println!("   Manual installation required. Check the project documentation.");
-        return Ok(());
-    };
+        let (names, skipped) = walk(true);
+        for name in ["link.py", "b_link", "b_link/two.py", "b_link/c/three.py"] {
+            assert!(names.iter().any(|n| n == name), "{} should be followed", name);
+        }
+        assert!(!names.iter().any(|name| name.contains("escape")));
+        let escaped: Vec<&str> = skipped.iter()
+            .filter(|(_, reason)| *reason == SkipReason::SymlinkEscapesRoot)
+            .map(|(path, _)| path.as_str())
+            .collect();
+        assert_eq!(escaped, ["escape.txt", "escape_dir"]);
+    }
 
-    if status.success() {
-        println!("✅ Successfully updated to the latest version!");
-        println!("   Please restart your terminal or reload your shell for the changes to take effect.");
-        Ok(())
-    } else {
-        Err(anyhow::anyhow!(
-            "Failed to install the latest version. Exit code: {}",
-            status.code().unwrap_or(-1)
-        ))
+    #[test]
+    fn proxy_urls_are_validated_and_used() {
+        assert!(parse_proxy("http://proxy.internal:3128").is_ok());
+        assert!(parse_proxy("HTTPS://proxy.internal").is_ok());
+        assert_eq!(parse_proxy("socks5h://127.0.0.1:1080").is_ok(), cfg!(feature = "socks"));
+        for bad in ["proxy.internal:3128", "ftp://proxy.internal"] {
+            assert!(parse_proxy(bad).unwrap_err().to_string().contains("Unsupported proxy"));
+        }
+
+        // Requests go to the proxy with the absolute target URL
+        let (proxy, server) = serve_once(200);
+        let client = client_builder(Some(&proxy)).unwrap().build().unwrap();
+        client.get("http://submissions.test/check").send().unwrap();
+        assert_eq!(server.join().unwrap(), "GET http://submissions.test/check HTTP/1.1");
     }
-}
 
-#[cfg(windows)]
-fn handle_windows_update(download_path: &Path, filename: &str) -> Result<()> {
-    let status = if filename.ends_with(".exe") {
-        // Run the installer executable
-        Command::new(download_path).status()?
-    } else if filename.ends_with(".msi") {
-        // Run the MSI installer
-        Command::new("msiexec").args(["/i", &download_path.to_string_lossy()]).status()?
-    } else if filename.ends_with(".bat") || filename.ends_with(".cmd") {
-        // Run Windows batch file
-        Command::new("cmd").args(["/C", &download_path.to_string_lossy()]).status()?
-    } else if filename.ends_with(".ps1") {
-        // Run PowerShell script
-        Command::new("powershell")
-            .args(["-ExecutionPolicy", "Bypass", "-File", &download_path.to_string_lossy()])
-            .status()?
-    } else if filename.ends_with(".zip") {
-        // For zip archives, give instructions
-        println!("📦 Downloaded archive. Manual extraction and installation required.");
-        println!("   Download saved to: {}", download_path.display());
-        println!("   You can extract this file and run any installation scripts inside.");
-        return Ok(());
-    } else {
-        // For any other file type
-        println!("📄 Downloaded file: {}", download_path.display());
-        println!("   Manual installation required. Check the project documentation.");
-        return Ok(());
-    };
+    #[test]
+    fn archive_sha256_is_sent_alongside_the_chosen_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("submission.zip");
+        std::fs::write(&path, "hello").unwrap();
+        let sha256 = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
 
-    if status.success() {
-        println!("✅ Successfully updated to the latest version!");
-        println!("   Please restart your command prompt or PowerShell for the changes to take effect.");
-        Ok(())
-    } else {
-        Err(anyhow::anyhow!(
-            "Failed to install the latest version. Exit code: {}",
-            status.code().unwrap_or(-1)
-        ))
+        let (digest, fields) = archive_checksum_fields(&path, HashAlgo::Sha256).unwrap();
+        assert_eq!(digest, sha256);
+        assert_eq!(fields, [
+            ("sha256", sha256.to_string()),
+            ("checksum", sha256.to_string()),
+            ("checksum_algo", "sha256".to_string()),
+        ]);
+
+        let (digest, fields) = archive_checksum_fields(&path, HashAlgo::Blake3).unwrap();
+        assert_eq!(digest, sha256);
+        assert_eq!(fields[0], ("sha256", sha256.to_string()));
+        assert_eq!(fields[1], ("checksum", file_digest(&path, HashAlgo::Blake3, false).unwrap()));
+        assert_eq!(fields[2], ("checksum_algo", "blake3".to_string()));
     }
-}
 
-#[cfg(not(any(unix, windows)))]
-fn handle_generic_update(download_path: &Path, filename: &str) -> Result<()> {
-    // Generic fallback for any other platform
-    println!("📥 Downloaded update file: {}", download_path.display());
-    println!("⚠️ Automatic installation not supported on this platform.");
-    println!("   Please follow the manual installation instructions from the project documentation.");
-    Ok(())
-}
+    #[test]
+    fn archives_need_at_least_one_file() {
+        ensure_files_collected(&named_entries(&["a/", "a/model.py"]), "py").unwrap();
+        let err = ensure_files_collected(&named_entries(&["a/", "a/b/"]), "py").unwrap_err();
+        assert!(err.to_string().starts_with("No files matched for format py after exclusions"));
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
-    
-    match &cli.command {
-        Commands::Init { config, api_key, competition_id } => {
-            create_config_file(config, api_key.clone(), competition_id.clone())?;
-        },
-        
-        Commands::Update { force } => {
-            match check_for_updates()? {
-                Some((_, download_url)) => {
-                    update_to_latest(&download_url, *force)?;
-                },
-                None => {
-                    // No update available
-                },
-            }
-        },
-        
-        Commands::Send { 
-            config, 
-            competition_id, 
-            api_key, 
-            server,
-            compression, 
-            force_format,
-            auto_confirm
-        } => {
-            // Load the configuration
-            let mut config_data = load_config(config)?;
-            
-            // Override config with command line arguments if provided
-            if let Some(api) = api_key {
-                config_data.api_key = api.clone();
+        // Excluding everything fails before any archive is written
+        let tree = nested_tree();
+        let out = tempfile::tempdir().unwrap();
+        let mut collect = test_collect(tree.path());
+        collect.exclude = vec!["*.py".to_string()];
+        let compression = compression_for_goal(OptimizeFor::Speed);
+        assert!(create_zip_archive(compression, &collect, 1, &EntryLayout::default(), out.path(), false).is_err());
+        assert!(create_targz_archive(compression, &collect, &EntryLayout::default(), out.path(), false).is_err());
+    }
+
+    #[test]
+    fn toml_json_and_yaml_configs_load_alike() {
+        let _env = lock_env();
+        assert_eq!(ConfigFormat::from_extension(Path::new("a/submission.YML")), Some(ConfigFormat::Yaml));
+        assert_eq!(ConfigFormat::from_extension(Path::new("submission.toml")), Some(ConfigFormat::Toml));
+        assert_eq!(ConfigFormat::from_extension(Path::new("submission.json")), Some(ConfigFormat::Json));
+        assert_eq!(ConfigFormat::from_extension(Path::new("submission")), None);
+
+        let dir = tempfile::tempdir().unwrap();
+        let files = [
+            ("submission.yml", "api_key: key\ncompetition_id: \"7\"\ncompression_level: 9\nexclude: [data]\n"),
+            ("submission.toml", "api_key = \"key\"\ncompetition_id = \"7\"\ncompression_level = 9\nexclude = [\"data\"]\n"),
+            ("submission.json", r#"{"api_key": "key", "competition_id": "7", "compression_level": 9, "exclude": ["data"]}"#),
+        ];
+        for (name, text) in files {
+            let path = dir.path().join(name);
+            std::fs::write(&path, text).unwrap();
+            let config = load_config(path.to_str().unwrap()).unwrap();
+            assert_eq!(config.api_key, "key", "{}", name);
+            assert_eq!(config.competition_id.as_deref(), Some("7"), "{}", name);
+            assert_eq!(config.compression_level, 9, "{}", name);
+            assert!(config.exclude.contains(&"data".to_string()), "{}", name);
+        }
+
+        let path = dir.path().join("broken.toml");
+        std::fs::write(&path, "api_key: key\n").unwrap();
+        let err = load_config(path.to_str().unwrap()).unwrap_err();
+        assert!(format!("{:#}", err).contains("as TOML"));
+    }
+
+    #[test]
+    fn init_writes_the_format_its_path_names() {
+        let _env = lock_env();
+        let dir = tempfile::tempdir().unwrap();
+        for (name, format) in [
+            ("submission.yaml", ConfigFormat::Yaml),
+            ("submission.toml", ConfigFormat::Toml),
+            ("submission.json", ConfigFormat::Json),
+        ] {
+            let path = dir.path().join(name);
+            create_config_file(path.to_str().unwrap(), Some("key".to_string()), Some("comp".to_string())).unwrap();
+            let text = std::fs::read_to_string(&path).unwrap();
+            assert!(parse_config_text(&text, format).is_ok(), "{}", name);
+
+            // What init writes loads back unchanged
+            let config = load_config(path.to_str().unwrap()).unwrap();
+            assert_eq!(config.api_key, "key", "{}", name);
+            assert_eq!(config.competition_id.as_deref(), Some("comp"), "{}", name);
+            assert!(config.exclude.contains(&"node_modules".to_string()), "{}", name);
+        }
+    }
+
+    #[test]
+    fn summary_counts_and_sizes() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_bytes(10 << 20), "10.0 MB");
+        assert_eq!(format_bytes(3 << 40), "3.0 TB");
+        assert_eq!(format_bytes(u64::MAX), "16777216.0 TB");
+
+        // The summary's counts come from what the builder wrote
+        let tree = nested_tree();
+        let out = tempfile::tempdir().unwrap();
+        let compression = compression_for_goal(OptimizeFor::Speed);
+        let built = create_zip_archive(compression, &test_collect(tree.path()), 1, &EntryLayout::default(), out.path(), false).unwrap();
+        assert_eq!((built.files.len(), built.dir_count), (4, 3));
+        let largest = built.files.iter().max_by_key(|f| f.size).unwrap();
+        assert_eq!((largest.path.as_str(), largest.size), ("a/b/c/three.py", 14));
+    }
+
+    #[test]
+    fn server_cooldown_counts_down_from_the_last_submission() {
+        assert_eq!(cooldown_remaining(1_000, 600, 1_000), 600);
+        assert_eq!(cooldown_remaining(1_000, 600, 1_250), 350);
+        assert_eq!(cooldown_remaining(1_000, 600, 1_600), 0);
+        assert_eq!(cooldown_remaining(1_000, 600, 9_000), 0);
+        // A last submission in the future never extends the wait
+        assert_eq!(cooldown_remaining(2_000, 600, 1_000), 600);
+
+        let check = |extra: &str| -> CheckResponse {
+            serde_json::from_str(&format!(
+                r#"{{"submission_approved": true, "required_format": "py", "remaining_attempts": 3, "last_submission_by_user": 1000{}}}"#,
+                extra
+            )).unwrap()
+        };
+        let cooling = check(r#", "cooldown_secs": 3900"#);
+        assert_eq!(cooling.cooldown_remaining(1_000), Some(Duration::from_secs(3_900)));
+        assert_eq!(cooling.cooldown_remaining(4_900), None);
+        assert_eq!(check("").cooldown_remaining(1_000), None);
+
+        assert_eq!(format_duration(Duration::from_secs(3_900)), "1h 5m");
+        assert_eq!(format_duration(Duration::from_secs(350)), "5m 50s");
+        assert_eq!(format_duration(Duration::from_secs(42)), "42s");
+    }
+
+    #[test]
+    fn endpoint_paths_join_with_one_slash() {
+        for (server, path) in [
+            ("https://host/api", "check"),
+            ("https://host/api/", "/check"),
+            ("https://host/api//", "//check"),
+        ] {
+            assert_eq!(endpoint_url(server, path).unwrap(), "https://host/api/check");
+        }
+        assert_eq!(endpoint_url("http://localhost:8000", "v2/submit").unwrap(), "http://localhost:8000/v2/submit");
+        assert!(endpoint_url("not a url", "check").unwrap_err().to_string().contains("Invalid endpoint URL"));
+
+        let _env = lock_env();
+        let config = parse_yaml_config("competition_id: comp\n");
+        assert_eq!((config.check_path.as_str(), config.submit_path.as_str()), ("/check", "/submit"));
+        let config = parse_yaml_config("check_path: /api/v1/check\nsubmit_path: api/v1/submit\n");
+        assert_eq!(endpoint_url("https://host/", &config.check_path).unwrap(), "https://host/api/v1/check");
+        assert_eq!(endpoint_url("https://host/", &config.submit_path).unwrap(), "https://host/api/v1/submit");
+
+        // A prefix applies to every endpoint, including the ones without a configurable path
+        let config = parse_yaml_config("api_prefix: /api/v2/\n");
+        assert_eq!(api_url("https://host/", &config, &config.check_path).unwrap(), "https://host/api/v2/check");
+        assert_eq!(
+            api_url("https://host/", &config, "/submissions/42/download").unwrap(),
+            "https://host/api/v2/submissions/42/download"
+        );
+        assert_eq!(api_url("https://host//", &config, "/competitions").unwrap(), "https://host/api/v2/competitions");
+        let (url, server) = serve_once(200);
+        check_server_health(&Client::new(), &url, &config).unwrap();
+        assert_eq!(server.join().unwrap(), "GET /api/v2/health HTTP/1.1");
+    }
+
+    #[test]
+    fn min_interval_waits_for_the_competitions_last_submission() {
+        let history: Vec<HistoryEntry> = serde_json::from_str(r#"[
+            {"timestamp": 1000, "competition_id": "comp"},
+            {"timestamp": 1500, "competition_id": "comp"},
+            {"timestamp": 1900, "competition_id": "other"}
+        ]"#).unwrap();
+        let interval = Duration::from_secs(600);
+
+        // Within the interval of the latest submission for this competition
+        assert_eq!(remaining_interval(&history, Some("comp"), interval, 1_600), Some(Duration::from_secs(500)));
+        // Elapsed, or no submission for the competition yet
+        assert_eq!(remaining_interval(&history, Some("comp"), interval, 2_100), None);
+        assert_eq!(remaining_interval(&history, Some("new"), interval, 1_600), None);
+        // A timestamp ahead of the clock counts as just now
+        assert_eq!(remaining_interval(&history, Some("other"), interval, 1_000), Some(interval));
+    }
+
+    /// Answer `requests` chunk uploads, each on its own connection, with the status `respond`
+    /// picks from the `Idempotency-Key` and how often that key was seen before. Returns the
+    /// URL, the keys received, and the most requests that were in flight at once.
+    fn serve_chunks(
+        requests: usize,
+        respond: fn(&str, usize) -> u16,
+    ) -> (String, thread::JoinHandle<(Vec<String>, usize)>) {
+        use std::io::{BufRead, BufReader};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let keys = Arc::new(Mutex::new(Vec::new()));
+            let in_flight = Arc::new(AtomicUsize::new(0));
+            let peak = Arc::new(AtomicUsize::new(0));
+            let handlers: Vec<_> = (0..requests).map(|_| {
+                let (stream, _) = listener.accept().unwrap();
+                let (keys, in_flight, peak) = (Arc::clone(&keys), Arc::clone(&in_flight), Arc::clone(&peak));
+                thread::spawn(move || {
+                    let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    let mut reader = BufReader::new(stream);
+                    let (mut key, mut length) = (String::new(), 0);
+                    let mut line = String::new();
+                    while reader.read_line(&mut line).unwrap() > 2 {
+                        let lower = line.to_ascii_lowercase();
+                        if let Some(value) = lower.strip_prefix("idempotency-key:") {
+                            key = value.trim().to_string();
+                        } else if let Some(value) = lower.strip_prefix("content-length:") {
+                            length = value.trim().parse().unwrap();
+                        }
+                        line.clear();
+                    }
+                    std::io::copy(&mut (&mut reader).take(length), &mut std::io::sink()).unwrap();
+                    thread::sleep(Duration::from_millis(100));
+
+                    let status = {
+                        let mut keys = keys.lock().unwrap();
+                        let seen = keys.iter().filter(|k| **k == key).count();
+                        keys.push(key.clone());
+                        respond(&key, seen)
+                    };
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                    write!(reader.get_mut(), "HTTP/1.1 {} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status).unwrap();
+                })
+            }).collect();
+            for handler in handlers {
+                handler.join().unwrap();
             }
-            
-            let comp_id = competition_id.as_deref().or(config_data.competition_id.as_deref());
-            
-            let server_url = match server {
-                Some(s) => s.clone(),
-                None => config_data.server_url.clone(),
-            };
-            
-            let comp_level = compression.unwrap_or(config_data.compression_level);
-            
-            let auto_confirm_submission = *auto_confirm || config_data.preferences.auto_confirm;
-            
-            // Determine the format to use - either from force_format, config, or server check
-            let format = if let Some(forced) = force_format {
-                println!("⚠️ Bypassing server check, using forced format: {}", forced);
-                forced.clone()
-            } else if let Some(config_format) = &config_data.format {
-                println!("⚠️ Using format from config file: {}", config_format);
-                config_format.clone()
-            } else {
-                // Contact the server to check for submission approval and format
-                let check_response = check_with_server(&server_url, &config_data.api_key, comp_id)?;
+            let keys = keys.lock().unwrap().clone();
+            (keys, peak.load(Ordering::SeqCst))
+        });
+        (url, handle)
+    }
+
+    fn chunk_upload_options(concurrency: usize) -> UploadOptions<'static> {
+        UploadOptions {
+            competition_id: None,
+            fields: &[],
+            content_type: None,
+            parallel_hash: Some(HashAlgo::Sha256),
+            keep_archive: true,
+            show_progress: false,
+            chunked: true,
+            concurrency,
+        }
+    }
+
+    #[test]
+    fn chunks_upload_concurrently_and_retry_on_their_own() {
+        let config = {
+            let _env = lock_env();
+            parse_yaml_config("api_key: key\n")
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("submission.zip");
+        let length = UPLOAD_CHUNK_BYTES * 2 + 1;
+        std::fs::write(&path, vec![7u8; length as usize]).unwrap();
 
-                // Check if submission is approved
-                if !check_response.submission_approved {
-                    println!("❌ Submission not allowed. No remaining attempts.");
-                    return Ok(());
+        // The second chunk is refused once and then accepted
+        let (url, server) = serve_chunks(4, |key, seen| if key.ends_with("-1") && seen == 0 { 503 } else { 200 });
+        let chunks = upload_chunks(&Client::new(), &path, &config, &url, length, &chunk_upload_options(3)).unwrap();
+        let (mut keys, peak) = server.join().unwrap();
+
+        assert_eq!(chunks.total, 3);
+        assert_eq!(chunks.digest, Some(sha256_file(&path).unwrap()));
+        keys.sort();
+        let id = &chunks.upload_id;
+        assert_eq!(keys, [format!("{}-0", id), format!("{}-1", id), format!("{}-1", id), format!("{}-2", id)]);
+        assert!(peak > 1, "chunks were sent one at a time");
+
+        // Every chunk is attempted and all rejections are reported together
+        let (url, server) = serve_chunks(3, |key, _| if key.ends_with("-1") { 200 } else { 400 });
+        let err = upload_chunks(&Client::new(), &path, &config, &url, length, &chunk_upload_options(2)).unwrap_err();
+        assert_eq!(server.join().unwrap().0.len(), 3);
+        let message = err.to_string();
+        assert!(message.starts_with("Failed to upload 2 of 3 chunks"), "{}", message);
+        assert!(message.contains("chunk 1/3 was rejected") && message.contains("chunk 3/3 was rejected"), "{}", message);
+    }
+
+    #[test]
+    fn fetch_only_keeps_verified_downloads() {
+        for bad in ["", "../x", "a/b", "id?x", &"9".repeat(129)] {
+            assert!(validate_submission_id(bad).is_err(), "{:?} should be rejected", bad);
+        }
+        validate_submission_id("sub_42-A").unwrap();
+
+        let config = {
+            let _env = lock_env();
+            parse_yaml_config("api_key: key\n")
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("submission-42.zip");
+        let sha256 = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+        let (url, server) = serve_once_with_body(200, "hello");
+        fetch_submission(&Client::new(), &url, &config, "42", &output, Some(sha256)).unwrap();
+        assert_eq!(server.join().unwrap(), "GET /submissions/42/download HTTP/1.1");
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "hello");
+        std::fs::remove_file(&output).unwrap();
+
+        // Neither a corrupt download nor an error response leaves a file behind
+        let (url, server) = serve_once_with_body(200, "tampered");
+        let err = fetch_submission(&Client::new(), &url, &config, "42", &output, Some(sha256)).unwrap_err();
+        server.join().unwrap();
+        assert!(err.to_string().contains("Checksum mismatch"));
+        let (url, server) = serve_once_with_body(500, "oops");
+        assert!(fetch_submission(&Client::new(), &url, &config, "42", &output, None).is_err());
+        server.join().unwrap();
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        assert!(fetch_submission(&Client::new(), "http://127.0.0.1:9", &config, "../42", &output, None).is_err());
+    }
+
+    /// Answer one request with 200 and return its body, decoding a chunked transfer encoding
+    fn serve_once_capturing_body() -> (String, thread::JoinHandle<String>) {
+        use std::io::{BufRead, BufReader};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let (mut length, mut chunked) = (None, false);
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                let lower = line.to_ascii_lowercase();
+                if let Some(value) = lower.strip_prefix("content-length:") {
+                    length = Some(value.trim().parse::<usize>().unwrap());
                 }
+                chunked |= lower.starts_with("transfer-encoding:") && lower.contains("chunked");
+                line.clear();
+            }
 
-                // Prompt the user for confirmation
-                if !auto_confirm_submission {
-                    let confirm_msg = format!(
-                        "Proceed with submission? You have {} attempts remaining.",
-                        check_response.remaining_attempts
-                    );
-
-                    let confirmed = Confirm::with_theme(&ColorfulTheme::default())
-                        .with_prompt(confirm_msg)
-                        .default(true)
-                        .interact()?;
-
-                    if !confirmed {
-                        println!("❌ Submission cancelled.");
-                        return Ok(());
+            let mut body = Vec::new();
+            if chunked {
+                loop {
+                    line.clear();
+                    reader.read_line(&mut line).unwrap();
+                    let size = usize::from_str_radix(line.trim(), 16).unwrap();
+                    let mut chunk = vec![0; size + 2];
+                    reader.read_exact(&mut chunk).unwrap();
+                    if size == 0 {
+                        break;
                     }
+                    body.extend_from_slice(&chunk[..size]);
                 }
-
-                check_response.required_format
-            };
-            
-            // Validate format is either "repo" or "py"
-            if format != "repo" && format != "py" {
-                return Err(anyhow::anyhow!(
-                    "Unsupported format: {}. Expected 'repo' or 'py'", 
-                    format
-                ));
+            } else {
+                body.resize(length.unwrap_or(0), 0);
+                reader.read_exact(&mut body).unwrap();
             }
-            
-            // Create zip archive based on the required format
-            let zip_path = create_zip_archive(comp_level, &format, &config_data.exclude)?;
-            println!("✅ Created zip archive at: {}", zip_path.display());
-            
-            // Send the zip file to the submit endpoint
-            let submit_url = format!("{}/submit", server_url);
-            send_zip_to_endpoint(&zip_path, &config_data.api_key, &submit_url, comp_id)?;
+            write!(reader.get_mut(), "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").unwrap();
+            String::from_utf8_lossy(&body).into_owned()
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn parallel_hash_still_sends_the_sha256_field() {
+        let config = {
+            let _env = lock_env();
+            parse_yaml_config("api_key: key\n")
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("submission.zip");
+        std::fs::write(&path, "hello").unwrap();
+        let sha256 = sha256_file(&path).unwrap();
+        let field = |body: &str, name: &str| {
+            let marker = format!("name=\"{}\"\r\n\r\n", name);
+            let start = body.find(&marker).unwrap_or_else(|| panic!("no {} field in {}", name, body)) + marker.len();
+            body[start..].split("\r\n").next().unwrap().to_string()
+        };
+
+        for algo in [HashAlgo::Sha256, HashAlgo::Blake3] {
+            let (url, server) = serve_once_capturing_body();
+            let upload = UploadOptions { parallel_hash: Some(algo), chunked: false, ..chunk_upload_options(1) };
+            let result = send_zip_to_endpoint(&Client::new(), &path, &config, &url, &upload).unwrap();
+            let body = server.join().unwrap();
+
+            assert_eq!(field(&body, "sha256"), sha256);
+            assert_eq!(field(&body, "checksum"), file_digest(&path, algo, false).unwrap());
+            assert_eq!(field(&body, "checksum_algo"), algo.as_str());
+            assert_eq!(result.archive_sha256, Some(sha256.clone()));
         }
     }
-    
-    Ok(())
-}
\ No newline at end of file
+
+    #[test]
+    fn review_reads_text_from_zip_and_targz_archives() {
+        let tree = nested_tree();
+        let out = tempfile::tempdir().unwrap();
+        let collect = test_collect(tree.path());
+        let compression = compression_for_goal(OptimizeFor::Speed);
+        let zip = create_zip_archive(compression, &collect, 1, &EntryLayout::default(), out.path(), false).unwrap();
+        let targz = create_targz_archive(compression, &collect, &EntryLayout::default(), out.path(), false).unwrap();
+
+        // The review cache keeps every format under a `.zip` name
+        let cached = out.path().join("cached.zip");
+        std::fs::copy(&targz.path, &cached).unwrap();
+        for path in [&zip.path, &targz.path, &cached] {
+            assert_eq!(read_archive_text(path, "a/b/two.py").as_deref(), Some("a/b/two.py"), "{}", path.display());
+            assert_eq!(read_archive_text(path, "missing.py"), None);
+        }
+    }
+}